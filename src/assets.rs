@@ -2,13 +2,21 @@ use bevy::prelude::*;
 
 #[derive(Default)]
 pub struct CommonAssets {
+    /// False until the startup system has loaded everything below. Headless
+    /// apps never load any assets, so nothing should try to draw with them.
+    pub loaded: bool,
+
     pub font: Handle<Font>,
 
     pub tiles: [Handle<StandardMaterial>; 4],
 
     pub conveyor_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
+    pub slow_conveyor_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
+    pub fast_conveyor_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
+    pub underground_conveyor_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
     pub item_mat: Handle<StandardMaterial>,
     pub claw_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
+    pub rail_mat: (Handle<StandardMaterial>, Handle<StandardMaterial>),
     pub spawner_mat: Handle<StandardMaterial>,
     pub destroyer_mat: Handle<StandardMaterial>,
 
@@ -37,17 +45,19 @@ fn startup(
     mut mesh_mats: ResMut<Assets<StandardMaterial>>,
     mut common_assets: ResMut<CommonAssets>,
 ) {
-    let mut make_mat = |filename: &str| {
+    let mut make_tinted_mat = |filename: &str, base_color: Color| {
         let tex = asset_server.load(filename);
         let mat = StandardMaterial {
             // TODO: Change this to Mask once Mask works.
             alpha_mode: AlphaMode::Blend,
+            base_color,
             base_color_texture: Some(tex),
             unlit: true,
             ..Default::default()
         };
         mesh_mats.add(mat)
     };
+    let mut make_mat = |filename: &str| make_tinted_mat(filename, Color::WHITE);
     common_assets.font = asset_server.load("LiberationMono-Regular.ttf");
     for (i, path) in [
         "tile.png",
@@ -66,6 +76,7 @@ fn startup(
     common_assets.conveyor_mat.1 = make_mat("conveyor_down.png");
     common_assets.item_mat = make_mat("item.png");
     common_assets.claw_mat = (make_mat("claw_open.png"), make_mat("claw_closed.png"));
+    common_assets.rail_mat = (make_mat("rail_up.png"), make_mat("rail_down.png"));
     common_assets.spawner_mat = make_mat("spawner.png");
     common_assets.destroyer_mat = make_mat("destroyer.png");
 
@@ -83,12 +94,30 @@ fn startup(
     common_assets.cursor_accept_mat = make_mat("cursor_accept.png");
     common_assets.cursor_deny_mat = make_mat("cursor_deny.png");
 
+    // The other conveyor tiers reuse the normal art in a different color.
+    let slow = Color::rgb(0.6, 0.6, 0.6);
+    common_assets.slow_conveyor_mat = (
+        make_tinted_mat("conveyor_up.png", slow),
+        make_tinted_mat("conveyor_down.png", slow),
+    );
+    let fast = Color::rgb(1.0, 0.6, 0.3);
+    common_assets.fast_conveyor_mat = (
+        make_tinted_mat("conveyor_up.png", fast),
+        make_tinted_mat("conveyor_down.png", fast),
+    );
+    let underground = Color::rgb(0.4, 0.4, 0.9);
+    common_assets.underground_conveyor_mat = (
+        make_tinted_mat("conveyor_up.png", underground),
+        make_tinted_mat("conveyor_down.png", underground),
+    );
+
     common_assets.clay_mat = mesh_mats.add(StandardMaterial {
         base_color: Color::rgb(1.0, 1.0, 1.0),
         ..Default::default()
     });
 
     common_assets.quad_mesh = meshes.add(shape::Quad::new(Vec2::ONE).into());
+    common_assets.loaded = true;
 }
 
 pub struct Plug;
@@ -3,25 +3,46 @@ pub mod claw;
 pub mod conveyor;
 pub mod destroyer;
 pub mod machine;
+mod registry;
 mod spawn;
 pub mod spawner;
+pub mod stats;
 mod support;
 pub mod storage;
 pub mod drill;
 
 use bevy::prelude::{App, Plugin};
 
-pub use self::{base::*, spawn::*, support::*};
+pub use self::{base::*, registry::*, spawn::*, support::*};
 
 pub struct Plug;
 
 impl Plugin for Plug {
     fn build(&self, app: &mut App) {
-        app.add_plugin(claw::Plug)
+        let mut registry = BuildableRegistry::default();
+        registry.register::<claw::BClaw>();
+        registry.register::<claw::BWaypointClaw>();
+        registry.register::<conveyor::BConveyor>();
+        registry.register::<conveyor::BFastConveyor>();
+        registry.register::<conveyor::BSlowConveyor>();
+        registry.register::<conveyor::BSorter>();
+        registry.register::<conveyor::BSplitter>();
+        registry.register::<conveyor::BUndergroundEntrance>();
+        registry.register::<conveyor::BUndergroundExit>();
+        registry.register::<destroyer::BDestroyer>();
+        registry.register::<drill::BDrill>();
+        registry.register::<machine::BMachine>();
+        registry.register::<spawner::BSpawner>();
+        registry.register::<storage::BSmallWarehouse>();
+
+        app.insert_resource(registry)
+            .add_plugin(claw::Plug)
             .add_plugin(conveyor::Plug)
             .add_plugin(destroyer::Plug)
+            .add_plugin(drill::Plug)
             .add_plugin(machine::Plug)
             .add_plugin(spawner::Plug)
+            .add_plugin(stats::Plug)
             .add_plugin(storage::Plug);
     }
 }
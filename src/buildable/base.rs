@@ -2,9 +2,11 @@ use std::fmt::Debug;
 
 use bevy::prelude::*;
 use dyn_clone::DynClone;
+use serde::{de::DeserializeOwned, Serialize};
 
 use super::{
-    storage::ItemList, BuildingComponentsContext, BuildingContext, BuildingMaps, WhichMap,
+    storage::ItemList, BuildingComponentsContext, BuildingContext, BuildingMaps, SavedBuildable,
+    WhichMap,
 };
 use crate::prelude::*;
 
@@ -22,8 +24,13 @@ pub struct BuildingDetails {
     pub cost: ItemList,
 }
 
-pub trait Buildable: Debug + DynClone + Sync + Send + 'static {
+pub trait Buildable: Debug + DynClone + Serialize + DeserializeOwned + Sync + Send + 'static {
     type ExtraData;
+    /// Identifies this kind of buildable in save files, so it must never
+    /// change once it has been used.
+    const ID: &'static str;
+    /// What this kind of buildable is called in menus.
+    const NAME: &'static str;
 
     fn details(
         &self,
@@ -39,11 +46,17 @@ pub trait Buildable: Debug + DynClone + Sync + Send + 'static {
         maps: &mut BuildingMaps,
     ) -> (Vec<Entity>, Self::ExtraData);
     fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity>;
+    /// What this particular buildable is called, which may be more specific
+    /// than the name of its kind.
+    fn display_name(&self) -> String {
+        Self::NAME.to_owned()
+    }
     #[allow(unused_variables)]
     fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {}
 }
 
 pub trait DynBuildable: Debug + DynClone + Sync + Send + 'static {
+    fn id(&self) -> &'static str;
     fn details(
         &self,
         position: IsoPos,
@@ -58,10 +71,16 @@ pub trait DynBuildable: Debug + DynClone + Sync + Send + 'static {
         maps: &mut BuildingMaps,
     ) -> Entity;
     fn dyn_spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity>;
+    fn display_name(&self) -> String;
+    fn saved(&self) -> SavedBuildable;
     fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps);
 }
 
 impl<B: Buildable> DynBuildable for B {
+    fn id(&self) -> &'static str {
+        B::ID
+    }
+
     fn details(
         &self,
         position: IsoPos,
@@ -87,7 +106,11 @@ impl<B: Buildable> DynBuildable for B {
             .insert(GlobalTransform::default())
             .id();
         let (extras, data) = self.spawn_extras(ctx, maps);
-        let art = self.spawn_art(ctx);
+        let art = if ctx.common_assets.loaded {
+            self.spawn_art(ctx)
+        } else {
+            vec![]
+        };
         for child in extras.into_iter().chain(art.into_iter()) {
             ctx.commands.entity(root).add_child(child);
         }
@@ -108,6 +131,17 @@ impl<B: Buildable> DynBuildable for B {
         Buildable::spawn_art(self, ctx)
     }
 
+    fn display_name(&self) -> String {
+        Buildable::display_name(self)
+    }
+
+    fn saved(&self) -> SavedBuildable {
+        SavedBuildable {
+            id: B::ID.to_owned(),
+            data: ron::ser::to_string(self).unwrap(),
+        }
+    }
+
     fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
         Buildable::on_destroy(self, ctx, maps)
     }
@@ -1,9 +1,14 @@
 mod buildable;
 mod logic;
+mod waypoint;
 
 use bevy::prelude::*;
 
-pub use self::buildable::BClaw;
+pub use self::{
+    buildable::{BClaw, ClawPlacementError, ClawStop, StopKind},
+    logic::ClawLogic,
+    waypoint::BWaypointClaw,
+};
 use super::{Buildable, BuildingComponentsContext, BuildingContext, WhichMap};
 use crate::prelude::*;
 
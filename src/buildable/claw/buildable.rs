@@ -1,29 +1,296 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
 use bevy::prelude::*;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 
 use super::{Buildable, BuildingComponentsContext, BuildingContext, WhichMap};
 use crate::{
     buildable::{claw::logic::ClawLogic, storage::ItemList, BuildingDetails, BuildingMaps},
     iso::GRID_EDGE_LENGTH,
-    item::ReferenceItem,
+    item::{ItemFilter, ReferenceItem},
     prelude::*,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BClaw {
     pub take_from: IsoPos,
+    /// Which items the claw picks up. Anything else is left where it is.
+    #[serde(default)]
+    pub filter: ItemFilter,
+    /// Claws with a higher priority get the first pick when several of them
+    /// share a container.
+    #[serde(default)]
+    pub priority: u8,
+}
+
+/// Why a claw cannot be placed between two positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClawPlacementError {
+    /// Both ends are in the same cell.
+    ZeroLength,
+    /// The ends are further apart than any claw can reach.
+    OutOfReach { length: u32, max: u8 },
+    /// There is nothing to take items from or put them into at this end.
+    NoContainer(IsoPos),
+}
+
+impl Display for ClawPlacementError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroLength => write!(f, "both ends are in the same place"),
+            Self::OutOfReach { length, max } => {
+                write!(f, "too far ({} cells, at most {})", length, max)
+            }
+            Self::NoContainer(..) => write!(f, "nothing to hold items there"),
+        }
+    }
+}
+
+/// Whether a claw picks items up or drops them off at a stop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopKind {
+    Pick,
+    Drop,
+}
+
+impl StopKind {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Pick => Self::Drop,
+            Self::Drop => Self::Pick,
+        }
+    }
+}
+
+impl Default for StopKind {
+    fn default() -> Self {
+        Self::Pick
+    }
 }
 
-fn distance(start: IsoPos, end: IsoPos) -> u8 {
+impl Display for StopKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pick => write!(f, "pick up"),
+            Self::Drop => write!(f, "drop off"),
+        }
+    }
+}
+
+/// One of the containers a claw moves between.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClawStop {
+    pub position: IsoPos,
+    pub kind: StopKind,
+    /// Which items are picked up or dropped off here. Anything else is left
+    /// where it is.
+    #[serde(default)]
+    pub filter: ItemFilter,
+}
+
+/// How many grid cells a gantry from `start` to `end` spans.
+fn distance(start: IsoPos, end: IsoPos) -> u32 {
     let distance = start.centroid_pos().distance(end.centroid_pos());
     let distance = distance + 0.01;
     let distance = distance / GRID_EDGE_LENGTH * 2.0;
-    assert!(distance >= 0.0 && distance < 256.0);
-    (distance + 0.3).floor() as u8
+    (distance + 0.3).floor() as u32
+}
+
+/// The length of the gantry between two stops. Placement is checked before a
+/// claw is built, but one from an older save might not fit the current
+/// limits, so this always gives something the claw can work with.
+pub(super) fn segment_length(start: IsoPos, end: IsoPos) -> u8 {
+    distance(start, end).clamp(1, u8::MAX as u32) as u8
+}
+
+/// The total length of rail running through all of `stops`.
+fn rail_length(stops: &[ClawStop]) -> u32 {
+    stops
+        .windows(2)
+        .map(|pair| distance(pair[0].position, pair[1].position))
+        .sum()
+}
+
+/// Claws cost more the longer their rail is.
+pub(super) fn claw_details(stops: &[ClawStop], position: IsoPos) -> BuildingDetails {
+    BuildingDetails {
+        // Only the end is claimed, and the claw map keeps every claw ending
+        // there, so that several claws can share a container at either end.
+        shape: vec![position],
+        maps: vec![WhichMap::Claws],
+        cost: ItemList::from_counts(hashmap![
+            ReferenceItem::IronLump.as_item() => 1 + rail_length(stops),
+            ReferenceItem::PureAnimus.as_item() => 3,
+        ]),
+    }
+}
+
+/// Checks that every stop has a container and that the claw can reach from
+/// each stop to the next.
+pub(super) fn check_stops(
+    stops: &[ClawStop],
+    maps: &BuildingMaps,
+) -> Result<(), ClawPlacementError> {
+    for stop in stops {
+        BClaw::check_end(stop.position, maps)?;
+    }
+    for pair in stops.windows(2) {
+        BClaw::check_segment(pair[0].position, pair[1].position)?;
+    }
+    Ok(())
+}
+
+/// Looks up the container at each stop and spawns the head which moves
+/// between them.
+pub(super) fn spawn_claw_extras(
+    stops: &[ClawStop],
+    ctx: &mut BuildingContext,
+    maps: &BuildingMaps,
+) -> (Vec<Entity>, (Vec<Option<Entity>>, Entity)) {
+    // A stop can be missing its container when a claw is loaded after the
+    // building there was destroyed, in which case the claw starts out
+    // unattached.
+    let containers = stops
+        .iter()
+        .map(|stop| maps.item_containers.get(stop.position).copied())
+        .collect();
+    // The head moves back and forth while the root, and the rail drawn under
+    // it, stays put.
+    let head = ctx
+        .commands
+        .spawn()
+        .insert_bundle(PbrBundle {
+            material: ctx.common_assets.claw_mat.0.clone(),
+            mesh: ctx.common_assets.quad_mesh.clone(),
+            transform: sprite_transform(),
+            ..Default::default()
+        })
+        .id();
+    (vec![head], (containers, head))
+}
+
+/// Rails are drawn over the buildings at either end but under items and the
+/// claw itself.
+const RAIL_HEIGHT: f32 = 0.05;
+
+/// Every cell from `start` to `end`, inclusive, along with the direction the
+/// rail runs in. Empty if the two are not on a shared line.
+fn rail_cells(start: IsoPos, end: IsoPos) -> Vec<(IsoPos, IsoDirection)> {
+    let along = [IsoAxis::A, IsoAxis::B, IsoAxis::C]
+        .iter()
+        .find_map(|&axis| Some((axis, start.steps_along(axis, end)?)));
+    let (axis, steps) = match along {
+        Some(along) => along,
+        None => return vec![],
+    };
+    let direction = if steps < 0 {
+        axis.negative_direction()
+    } else {
+        axis.positive_direction()
+    };
+    (0..=steps.abs())
+        .map(|step| (start.offset_direction(direction, step), direction))
+        .collect()
+}
+
+/// Draws the rail between every pair of consecutive stops.
+pub(super) fn spawn_rail_art(stops: &[ClawStop], ctx: &mut BuildingContext) -> Vec<Entity> {
+    let mut drawn = HashSet::new();
+    let mut art = Vec::new();
+    for pair in stops.windows(2) {
+        for (position, direction) in rail_cells(pair[0].position, pair[1].position) {
+            // Stops are shared by the rails on both sides of them.
+            if !drawn.insert(position) {
+                continue;
+            }
+            // Picked the same way as conveyor art.
+            let material = if position.points_left() != direction.is_negative() {
+                ctx.common_assets.rail_mat.0.clone()
+            } else {
+                ctx.common_assets.rail_mat.1.clone()
+            };
+            let mut transform = position.building_transform(direction.axis()) * sprite_transform();
+            transform.translation.z = RAIL_HEIGHT;
+            art.push(
+                ctx.commands
+                    .spawn()
+                    .insert_bundle(PbrBundle {
+                        material,
+                        mesh: ctx.common_assets.quad_mesh.clone(),
+                        transform,
+                        ..Default::default()
+                    })
+                    .id(),
+            );
+        }
+    }
+    art
+}
+
+impl BClaw {
+    /// How many grid cells the longest claw can span.
+    pub const MAX_REACH: u8 = 8;
+    pub const MAX_PRIORITY: u8 = 3;
+
+    /// Checks that `position` has a container for one end of a claw.
+    pub fn check_end(position: IsoPos, maps: &BuildingMaps) -> Result<(), ClawPlacementError> {
+        if maps.item_containers.is_occupied(position) {
+            Ok(())
+        } else {
+            Err(ClawPlacementError::NoContainer(position))
+        }
+    }
+
+    /// Checks that a claw can reach from `start` to `end`, returning the
+    /// length of the gantry between them.
+    pub fn check_segment(start: IsoPos, end: IsoPos) -> Result<u8, ClawPlacementError> {
+        match distance(start, end) {
+            0 => Err(ClawPlacementError::ZeroLength),
+            length if length > Self::MAX_REACH as u32 => Err(ClawPlacementError::OutOfReach {
+                length,
+                max: Self::MAX_REACH,
+            }),
+            length => Ok(length as u8),
+        }
+    }
+
+    /// Checks that a claw can be placed from `take_from` to `position`,
+    /// returning the length of its gantry.
+    pub fn check_placement(
+        &self,
+        position: IsoPos,
+        maps: &BuildingMaps,
+    ) -> Result<u8, ClawPlacementError> {
+        Self::check_end(self.take_from, maps)?;
+        Self::check_end(position, maps)?;
+        Self::check_segment(self.take_from, position)
+    }
+
+    /// A claw is a waypoint claw with only two stops.
+    fn stops(&self, position: IsoPos) -> [ClawStop; 2] {
+        [
+            ClawStop {
+                position: self.take_from,
+                kind: StopKind::Pick,
+                filter: self.filter.clone(),
+            },
+            ClawStop {
+                position,
+                kind: StopKind::Drop,
+                filter: ItemFilter::Any,
+            },
+        ]
+    }
 }
 
 impl Buildable for BClaw {
-    type ExtraData = (Entity, Entity);
+    type ExtraData = (Vec<Option<Entity>>, Entity);
+    const ID: &'static str = "claw";
+    const NAME: &'static str = "Claw";
 
     fn details(
         &self,
@@ -31,39 +298,17 @@ impl Buildable for BClaw {
         direction: IsoDirection,
         maps: &BuildingMaps,
     ) -> Option<BuildingDetails> {
-        let length = distance(self.take_from, position) as u32;
-        Some(BuildingDetails {
-            shape: vec![self.take_from, position],
-            maps: vec![WhichMap::Claws],
-            cost: ItemList::from_counts(hashmap![
-                ReferenceItem::IronLump.as_item() => 1 + length,
-                ReferenceItem::PureAnimus.as_item() => 3,
-            ]),
-        })
+        Some(claw_details(&self.stops(position), position))
     }
 
     fn extra_root_components(
         &self,
         ctx: &mut BuildingComponentsContext,
-        (take_from, move_to): Self::ExtraData,
+        (containers, head): Self::ExtraData,
     ) {
-        let length = distance(self.take_from, ctx.position);
-        assert!(length >= 1);
-        ctx.commands
-            .insert(ClawLogic {
-                take_from,
-                move_to,
-                held_item: None,
-                length,
-                current_anim_tick: 0,
-                blocked: true,
-            })
-            .insert_bundle(PbrBundle {
-                material: ctx.common_assets.claw_mat.0.clone(),
-                mesh: ctx.common_assets.quad_mesh.clone(),
-                transform: sprite_transform(),
-                ..Default::default()
-            });
+        let stops = self.stops(ctx.position);
+        let logic = ClawLogic::new(&stops, containers, head, self.priority);
+        ctx.commands.insert(logic);
     }
 
     fn spawn_extras(
@@ -71,12 +316,25 @@ impl Buildable for BClaw {
         ctx: &mut BuildingContext,
         maps: &mut BuildingMaps,
     ) -> (Vec<Entity>, Self::ExtraData) {
-        let take_from = *maps.item_containers.get(self.take_from).unwrap();
-        let move_to = *maps.item_containers.get(ctx.position).unwrap();
-        (vec![], (take_from, move_to))
+        spawn_claw_extras(&self.stops(ctx.position), ctx, maps)
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        spawn_rail_art(&self.stops(ctx.position), ctx)
     }
 
-    fn spawn_art(&self, _ctx: &mut BuildingContext) -> Vec<Entity> {
-        vec![]
+    fn display_name(&self) -> String {
+        let mut details = Vec::new();
+        if self.filter != ItemFilter::Any {
+            details.push(self.filter.to_string());
+        }
+        if self.priority > 0 {
+            details.push(format!("priority {}", self.priority));
+        }
+        if details.is_empty() {
+            Self::NAME.to_owned()
+        } else {
+            format!("{} ({})", Self::NAME, details.join(", "))
+        }
     }
 }
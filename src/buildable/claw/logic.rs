@@ -1,93 +1,361 @@
+use std::cmp::Reverse;
+
 use bevy::prelude::*;
 
+use super::buildable::{segment_length, ClawStop, StopKind};
 use crate::{
-    item::{ItemAnimator, ItemContainer},
+    buildable::storage::{Storage, StorageOutput},
+    iso::ItemContainerMap,
+    item::{spawn_item, ItemAnimator, ItemContainer, ItemContainerAlignment, ItemFilter},
     prelude::*,
 };
 
+/// A stop along with what the claw knows about it while running.
+pub(super) struct Stop {
+    /// The container at the stop, or None if the building it belonged to was
+    /// destroyed.
+    container: Option<Entity>,
+    /// Where to look for a new container once the claw has lost its old one.
+    position: IsoPos,
+    kind: StopKind,
+    filter: ItemFilter,
+    /// How many ticks into its trip the claw reaches this stop.
+    arrival: u16,
+}
+
 #[derive(Component)]
-pub(super) struct ClawLogic {
-    pub(super) take_from: Entity,
-    pub(super) move_to: Entity,
+pub struct ClawLogic {
+    /// Visited in order, after which the claw goes back along its rail to
+    /// the first stop without stopping on the way.
+    pub(super) stops: Vec<Stop>,
+    /// The sprite which moves along the rail.
+    pub(super) head: Entity,
     pub(super) held_item: Option<Entity>,
-    /// Length of the gantry in grid cells.
-    pub(super) length: u8,
-    pub(super) current_anim_tick: u8,
+    pub(super) priority: u8,
+    /// The last tick this claw picked up or put down an item. Claws which
+    /// have waited longer go first when they share a container.
+    pub(super) last_served: u64,
+    pub(super) current_anim_tick: u16,
     pub(super) blocked: bool,
 }
 // How long it takes for the claw to traverse a segment of its path.
 const SEGMENT_DURATION: u8 = 4;
 
 impl ClawLogic {
+    pub(super) fn new(
+        stops: &[ClawStop],
+        containers: Vec<Option<Entity>>,
+        head: Entity,
+        priority: u8,
+    ) -> Self {
+        let mut arrival = 0;
+        let mut previous = None;
+        let stops = stops
+            .iter()
+            .zip(containers)
+            .map(|(stop, container)| {
+                if let Some(previous) = previous {
+                    let length = segment_length(previous, stop.position);
+                    arrival += length as u16 * SEGMENT_DURATION as u16;
+                }
+                previous = Some(stop.position);
+                Stop {
+                    container,
+                    position: stop.position,
+                    kind: stop.kind,
+                    filter: stop.filter.clone(),
+                    arrival,
+                }
+            })
+            .collect();
+        Self {
+            stops,
+            head,
+            held_item: None,
+            priority,
+            last_served: 0,
+            current_anim_tick: 0,
+            blocked: true,
+        }
+    }
+
+    pub fn current_anim_tick(&self) -> u16 {
+        self.current_anim_tick
+    }
+
+    pub fn head(&self) -> Entity {
+        self.head
+    }
+
+    pub fn held_item(&self) -> Option<Entity> {
+        self.held_item
+    }
+
+    pub fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    pub fn last_served(&self) -> u64 {
+        self.last_served
+    }
+
+    /// False while any stop is missing its container.
+    pub fn is_attached(&self) -> bool {
+        self.stops.iter().all(|stop| stop.container.is_some())
+    }
+
+    /// Puts the claw back into a previously saved state.
+    pub fn restore(
+        &mut self,
+        current_anim_tick: u16,
+        blocked: bool,
+        held_item: Option<Entity>,
+        last_served: u64,
+    ) {
+        self.current_anim_tick = current_anim_tick;
+        self.blocked = blocked;
+        self.held_item = held_item;
+        self.last_served = last_served;
+    }
+
+    /// Forgets any container that no longer exists and picks up whatever
+    /// container is now at the position of a stop missing one.
+    fn reattach(
+        &mut self,
+        container_map: &ItemContainerMap,
+        mut exists: impl FnMut(Entity) -> bool,
+    ) {
+        for stop in &mut self.stops {
+            stop.container =
+                find_container(stop.container, stop.position, container_map, &mut exists);
+        }
+    }
+
+    /// Claws are ticked in ascending order of this key, so that the claw with
+    /// the highest priority, and then the one which has waited the longest,
+    /// gets to an item or a free container first. The positions of the
+    /// claw's stops break any remaining ties, since several claws can end in
+    /// the same place.
+    fn tick_order(&self) -> (Reverse<u8>, u64, Vec<IsoPos>) {
+        (
+            Reverse(self.priority),
+            self.last_served,
+            self.stops.iter().rev().map(|stop| stop.position).collect(),
+        )
+    }
+
+    /// How many ticks it takes to get to the last stop.
+    fn one_way_length(&self) -> u16 {
+        self.stops.last().map_or(0, |stop| stop.arrival)
+    }
+
     /// How many ticks it takes to make a two-way trip.
-    fn anim_length(&self) -> u8 {
-        // *2 for round trip instead of one way.
-        self.length * SEGMENT_DURATION * 2
+    fn anim_length(&self) -> u16 {
+        // *2 for round trip instead of one way. A claw with a single stop
+        // would otherwise never move at all.
+        (self.one_way_length() * 2).max(1)
     }
 }
 
 pub(super) fn tick(
-    mut claws: Query<(&mut ClawLogic, &mut Handle<StandardMaterial>)>,
+    mut commands: Commands,
+    tick_clock: Res<TickClock>,
+    container_map: Res<ItemContainerMap>,
+    mut claws: Query<(Entity, &mut ClawLogic)>,
+    mut heads: Query<&mut Handle<StandardMaterial>>,
     mut containers: Query<(&mut ItemContainer, &IsoPos)>,
     mut items: Query<&mut ItemAnimator>,
+    item_kinds: Query<&Item>,
+    storage_outputs: Query<&Parent, With<StorageOutput>>,
+    mut storages: Query<&mut Storage>,
     common_assets: Res<CommonAssets>,
 ) {
-    for (mut claw, mut mat) in claws.iter_mut() {
+    // Query order is arbitrary, which would let the same claw win every time
+    // a container is contended.
+    let mut claw_ids: Vec<_> = claws
+        .iter_mut()
+        .map(|(id, claw)| (claw.tick_order(), id))
+        .collect();
+    claw_ids.sort();
+    for (_, id) in claw_ids {
+        let mut claw = claws.get_component_mut::<ClawLogic>(id).unwrap();
+        let claw = &mut *claw;
+        let mut mat = heads.get_mut(claw.head).unwrap();
+        claw.reattach(&container_map, |id| containers.get_mut(id).is_ok());
+        if !claw.is_attached() {
+            detach(claw, &mut commands, &mut containers, &mut items);
+            *mat = common_assets.claw_mat.0.clone();
+            continue;
+        }
         let anim_length = claw.anim_length();
         if !claw.blocked {
             claw.current_anim_tick = (claw.current_anim_tick + 1) % anim_length;
         }
         claw.blocked = false;
-        if claw.current_anim_tick == 0 {
-            // Trying to pick up an item.
-            let mut from = containers
-                .get_component_mut::<ItemContainer>(claw.take_from)
-                .unwrap();
-            if let Some(item) = from.try_take() {
-                claw.held_item = Some(item);
-                *mat = common_assets.claw_mat.1.clone();
-            } else {
-                claw.blocked = true;
+        // Nothing happens on the way back to the first stop.
+        let current_tick = claw.current_anim_tick;
+        let stop = match claw.stops.iter().find(|stop| stop.arrival == current_tick) {
+            Some(stop) => stop,
+            None => continue,
+        };
+        let container = stop.container.unwrap();
+        match (stop.kind, claw.held_item) {
+            (StopKind::Pick, None) => {
+                let mut from = containers
+                    .get_component_mut::<ItemContainer>(container)
+                    .unwrap();
+                let taken = match from.item() {
+                    Some(item) if stop.filter.allows(item_kinds.get(item).unwrap()) => {
+                        from.try_take()
+                    }
+                    // Items the filter does not allow are left where they
+                    // are, and the claw carries on instead of waiting for
+                    // them to be taken away.
+                    Some(_) => continue,
+                    None => {
+                        pull_from_storage(container, &stop.filter, &storage_outputs, &mut storages)
+                            .map(|item| {
+                                spawn_item(
+                                    &mut commands,
+                                    &common_assets,
+                                    item,
+                                    stop.position,
+                                    ItemContainerAlignment::Centroid,
+                                )
+                            })
+                    }
+                };
+                if let Some(item) = taken {
+                    claw.held_item = Some(item);
+                    claw.last_served = tick_clock.current_tick();
+                    *mat = common_assets.claw_mat.1.clone();
+                } else {
+                    claw.blocked = true;
+                }
             }
-        } else if claw.current_anim_tick == anim_length / 2 {
-            if let Ok((mut to, to_pos)) = containers.get_mut(claw.move_to) {
+            (StopKind::Drop, Some(item)) if stop.filter.allows(item_kinds.get(item).unwrap()) => {
+                let (mut to, to_pos) = containers.get_mut(container).unwrap();
                 to.try_put_from(&mut claw.held_item, *to_pos, &mut items);
                 claw.blocked = claw.held_item.is_some();
                 if !claw.blocked {
+                    claw.last_served = tick_clock.current_tick();
                     *mat = common_assets.claw_mat.0.clone();
                 }
             }
+            // Already holding something, or nothing to drop off here, so the
+            // claw moves straight on to the next stop.
+            _ => (),
+        }
+    }
+}
+
+/// Takes an item the filter allows out of the warehouse `container` belongs
+/// to, if it is one of the warehouse's outputs.
+fn pull_from_storage(
+    container: Entity,
+    filter: &ItemFilter,
+    storage_outputs: &Query<&Parent, With<StorageOutput>>,
+    storages: &mut Query<&mut Storage>,
+) -> Option<Item> {
+    let &Parent(warehouse) = storage_outputs.get(container).ok()?;
+    let mut storage = storages.get_mut(warehouse).ok()?;
+    match filter {
+        ItemFilter::Only(known) => {
+            let item = known.as_item();
+            (storage.remove_bulk(&item, 1) == 1).then(|| item)
+        }
+        _ => storage.remove_first(|item| filter.allows(item)),
+    }
+}
+
+/// Returns `current` if it still exists, or else the container at `pos`.
+fn find_container(
+    current: Option<Entity>,
+    pos: IsoPos,
+    container_map: &ItemContainerMap,
+    exists: &mut impl FnMut(Entity) -> bool,
+) -> Option<Entity> {
+    if let Some(current) = current.filter(|&container| exists(container)) {
+        return Some(current);
+    }
+    container_map
+        .get(pos)
+        .copied()
+        .filter(|&container| exists(container))
+}
+
+/// Puts the claw back at the start of its trip without anything in its hand.
+/// A held item goes back to the first stop it could have come from which has
+/// room for it, and is thrown away otherwise.
+fn detach(
+    claw: &mut ClawLogic,
+    commands: &mut Commands,
+    containers: &mut Query<(&mut ItemContainer, &IsoPos)>,
+    items: &mut Query<&mut ItemAnimator>,
+) {
+    let pickups = claw
+        .stops
+        .iter()
+        .filter(|stop| stop.kind == StopKind::Pick)
+        .filter_map(|stop| stop.container);
+    for container in pickups {
+        if let Ok((mut from, &from_pos)) = containers.get_mut(container) {
+            from.try_put_from(&mut claw.held_item, from_pos, items);
+        }
+    }
+    if let Some(item) = claw.held_item.take() {
+        commands.entity(item).despawn();
+    }
+    claw.current_anim_tick = 0;
+    claw.blocked = true;
+}
+
+/// Where along its rail a claw is after `ticks` ticks of its trip, given
+/// where each of its stops is. Unlike items turning on belts, claws go
+/// straight from stop to stop, because they have to reach each stop to pick
+/// up or drop off there.
+fn point_on_rail(stops: &[Stop], points: &[Vec2], ticks: f32) -> Vec2 {
+    for (index, pair) in stops.windows(2).enumerate() {
+        let (start, end) = (pair[0].arrival as f32, pair[1].arrival as f32);
+        if ticks <= end {
+            let progress = ((ticks - start) / (end - start)).max(0.0);
+            return points[index].lerp(points[index + 1], progress);
         }
     }
+    points.last().copied().unwrap_or_default()
 }
 
 pub(super) fn animate(
     tick_clock: Res<TickClock>,
-    mut claws: Query<(&ClawLogic, &mut Transform)>,
+    claws: Query<&ClawLogic>,
+    mut heads: Query<&mut Transform>,
     item_containers: Query<(&ItemContainer, &IsoPos)>,
     mut items: Query<(&mut ItemAnimator,)>,
 ) {
-    for (claw, mut transform) in claws.iter_mut() {
-        let from = item_containers.get(claw.take_from);
-        let to = item_containers.get(claw.move_to);
-        if from.is_err() || to.is_err() {
-            continue;
-        }
-        let (from_container, from_pos) = from.unwrap();
-        let (to_container, to_pos) = to.unwrap();
-        let from_pos = from_container.alignment().get_item_pos(*from_pos);
-        let to_pos = to_container.alignment().get_item_pos(*to_pos);
-        let anim_length = claw.anim_length();
-        let current_tick = claw.current_anim_tick;
-        let mut progress = current_tick as f32;
-        if !claw.blocked && !cfg!(feature = "no-interpolation") {
+    for claw in claws.iter() {
+        let points: Option<Vec<Vec2>> = claw
+            .stops
+            .iter()
+            .map(|stop| {
+                let (container, pos) = item_containers.get(stop.container?).ok()?;
+                Some(container.alignment().get_item_pos(*pos))
+            })
+            .collect();
+        // Unattached claws stay wherever they were.
+        let points = match points {
+            Some(points) => points,
+            None => continue,
+        };
+        let mut progress = claw.current_anim_tick as f32;
+        if !claw.blocked {
             progress += tick_clock.get_tick_progress();
         }
-        progress /= anim_length as f32 / 2.0;
-        if progress > 1.0 {
-            progress = 2.0 - progress;
+        let one_way_length = claw.one_way_length() as f32;
+        if progress > one_way_length {
+            progress = one_way_length * 2.0 - progress;
         }
-        let position_now = from_pos.lerp(to_pos, progress);
+        let position_now = point_on_rail(&claw.stops, &points, progress);
+        let mut transform = heads.get_mut(claw.head).unwrap();
         transform.translation = (position_now, 0.2).into();
         if let Some(item) = claw.held_item {
             items
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    buildable::{check_stops, claw_details, spawn_claw_extras, spawn_rail_art},
+    logic::ClawLogic,
+    ClawPlacementError, ClawStop,
+};
+use crate::{
+    buildable::{
+        Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails, BuildingMaps,
+    },
+    prelude::*,
+};
+
+/// A claw which works its way through a list of stops, picking up or
+/// dropping off items at each one, before heading back to the first. It is
+/// built at its last stop.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BWaypointClaw {
+    pub stops: Vec<ClawStop>,
+    #[serde(default)]
+    pub priority: u8,
+}
+
+impl BWaypointClaw {
+    /// Checks that every stop has a container and that each stop can be
+    /// reached from the one before it.
+    pub fn check_placement(&self, maps: &BuildingMaps) -> Result<(), ClawPlacementError> {
+        check_stops(&self.stops, maps)
+    }
+}
+
+impl Buildable for BWaypointClaw {
+    type ExtraData = (Vec<Option<Entity>>, Entity);
+    const ID: &'static str = "waypoint_claw";
+    const NAME: &'static str = "Waypoint Claw";
+
+    fn details(
+        &self,
+        position: IsoPos,
+        direction: IsoDirection,
+        maps: &BuildingMaps,
+    ) -> Option<BuildingDetails> {
+        Some(claw_details(&self.stops, position))
+    }
+
+    fn extra_root_components(
+        &self,
+        ctx: &mut BuildingComponentsContext,
+        (containers, head): Self::ExtraData,
+    ) {
+        let logic = ClawLogic::new(&self.stops, containers, head, self.priority);
+        ctx.commands.insert(logic);
+    }
+
+    fn spawn_extras(
+        &self,
+        ctx: &mut BuildingContext,
+        maps: &mut BuildingMaps,
+    ) -> (Vec<Entity>, Self::ExtraData) {
+        spawn_claw_extras(&self.stops, ctx, maps)
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        spawn_rail_art(&self.stops, ctx)
+    }
+
+    fn display_name(&self) -> String {
+        let mut name = format!("{} ({} stops", Self::NAME, self.stops.len());
+        if self.priority > 0 {
+            name.push_str(&format!(", priority {}", self.priority));
+        }
+        name.push(')');
+        name
+    }
+}
@@ -1,27 +1,87 @@
+mod sorter;
+mod splitter;
+mod tests;
+mod underground;
+
+use std::collections::HashSet;
+
 use bevy::prelude::*;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 
+pub use self::{
+    sorter::BSorter,
+    splitter::{BSplitter, SplitterLogic},
+    underground::{
+        BUndergroundEntrance, BUndergroundExit, UndergroundEntrance, MAX_UNDERGROUND_LENGTH,
+    },
+};
+use self::{sorter::SorterLogic, underground::UndergroundExit};
 use super::{
     storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
-    BuildingMaps, WhichMap,
+    BuildingMaps, DynBuildable, WhichMap,
 };
 use crate::{
-    item::{ItemAnimator, ItemContainer, ItemContainerAlignment, ReferenceItem},
+    iso::ItemContainerMap,
+    item::{ItemAnimator, ItemContainer, ItemContainerAlignment, OutputContainer, ReferenceItem},
     prelude::*,
 };
 
-#[derive(Clone, Debug)]
-pub struct BConveyor;
+/// How fast a conveyor moves items. Every tier is a buildable of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConveyorTier {
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl ConveyorTier {
+    /// It takes this many ticks for an item to ride one unit of a conveyor of
+    /// this tier.
+    pub fn duration(self) -> u8 {
+        match self {
+            Self::Slow => 8,
+            Self::Normal => 4,
+            Self::Fast => 2,
+        }
+    }
 
-impl Buildable for BConveyor {
-    type ExtraData = ();
+    pub fn buildable(self) -> Box<dyn DynBuildable> {
+        match self {
+            Self::Slow => Box::new(BSlowConveyor),
+            Self::Normal => Box::new(BConveyor),
+            Self::Fast => Box::new(BFastConveyor),
+        }
+    }
 
-    fn details(
-        &self,
-        position: IsoPos,
-        direction: IsoDirection,
-        maps: &BuildingMaps,
-    ) -> Option<BuildingDetails> {
+    fn cost(self) -> ItemList {
+        ItemList::from_counts(match self {
+            Self::Slow => hashmap![
+                ReferenceItem::IronLump.as_item() => 1,
+            ],
+            Self::Normal => hashmap![
+                ReferenceItem::IronLump.as_item() => 1,
+                ReferenceItem::PureAnimus.as_item() => 1,
+            ],
+            Self::Fast => hashmap![
+                ReferenceItem::IronLump.as_item() => 2,
+                ReferenceItem::PureAnimus.as_item() => 3,
+            ],
+        })
+    }
+
+    fn materials(
+        self,
+        assets: &CommonAssets,
+    ) -> &(Handle<StandardMaterial>, Handle<StandardMaterial>) {
+        match self {
+            Self::Slow => &assets.slow_conveyor_mat,
+            Self::Normal => &assets.conveyor_mat,
+            Self::Fast => &assets.fast_conveyor_mat,
+        }
+    }
+
+    fn details(self, position: IsoPos) -> Option<BuildingDetails> {
         Some(BuildingDetails {
             shape: vec![position],
             maps: vec![
@@ -29,108 +89,357 @@ impl Buildable for BConveyor {
                 WhichMap::Conveyors,
                 WhichMap::ItemContainers,
             ],
-            cost: ItemList::from_counts(hashmap![
-                ReferenceItem::IronLump.as_item() => 1,
-                ReferenceItem::PureAnimus.as_item() => 1,
-            ]),
+            cost: self.cost(),
         })
     }
 
-    fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+    fn extra_root_components(self, ctx: &mut BuildingComponentsContext) {
         ctx.commands
-            .insert(ConveyorLogic::default())
+            .insert(ConveyorLogic::new(self))
             .insert(ItemContainer::new_empty(
                 ItemContainerAlignment::AxisAligned(ctx.direction.axis()),
             ))
             .insert(SetupNeeded);
     }
 
-    fn spawn_extras(
-        &self,
-        _ctx: &mut BuildingContext,
-        _maps: &mut BuildingMaps,
-    ) -> (Vec<Entity>, Self::ExtraData) {
-        (vec![], ())
+    fn spawn_art(self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        let assets = ctx.common_assets;
+        spawn_belt_art(ctx, self.materials(assets))
     }
+}
 
-    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
-        // xor
-        let material = if ctx.position.points_left() != ctx.direction.is_negative() {
-            ctx.common_assets.conveyor_mat.0.clone()
-        } else {
-            ctx.common_assets.conveyor_mat.1.clone()
-        };
-        vec![ctx
-            .commands
-            .spawn()
-            .insert_bundle(PbrBundle {
-                material,
-                mesh: ctx.common_assets.quad_mesh.clone(),
-                transform: ctx.position.building_transform(ctx.direction.axis())
-                    * sprite_transform(),
-                ..Default::default()
-            })
-            .id()]
-    }
-
-    fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
-        for pos in ctx.position.surroundings() {
-            if let Some(&conveyor) = maps.conveyors.get(pos) {
-                ctx.commands.entity(conveyor).insert(SetupNeeded);
+/// Draws a single conveyor cell with one of a pair of up and down materials.
+fn spawn_belt_art(
+    ctx: &mut BuildingContext,
+    materials: &(Handle<StandardMaterial>, Handle<StandardMaterial>),
+) -> Vec<Entity> {
+    // xor
+    let material = if ctx.position.points_left() != ctx.direction.is_negative() {
+        materials.0.clone()
+    } else {
+        materials.1.clone()
+    };
+    vec![ctx
+        .commands
+        .spawn()
+        .insert_bundle(PbrBundle {
+            material,
+            mesh: ctx.common_assets.quad_mesh.clone(),
+            transform: ctx.position.building_transform(ctx.direction.axis()) * sprite_transform(),
+            ..Default::default()
+        })
+        .id()]
+}
+
+/// Declares the buildable for one conveyor tier. They only differ in their
+/// names and in which tier they pass everything on to.
+macro_rules! conveyor_buildable {
+    ($name:ident, $tier:expr, $id:literal, $display_name:literal) => {
+        #[derive(Clone, Debug, Serialize, Deserialize)]
+        pub struct $name;
+
+        impl Buildable for $name {
+            type ExtraData = ();
+            const ID: &'static str = $id;
+            const NAME: &'static str = $display_name;
+
+            fn details(
+                &self,
+                position: IsoPos,
+                _direction: IsoDirection,
+                _maps: &BuildingMaps,
+            ) -> Option<BuildingDetails> {
+                $tier.details(position)
+            }
+
+            fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+                $tier.extra_root_components(ctx);
+            }
+
+            fn spawn_extras(
+                &self,
+                _ctx: &mut BuildingContext,
+                _maps: &mut BuildingMaps,
+            ) -> (Vec<Entity>, Self::ExtraData) {
+                (vec![], ())
+            }
+
+            fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+                $tier.spawn_art(ctx)
+            }
+        }
+    };
+}
+
+conveyor_buildable!(BConveyor, ConveyorTier::Normal, "conveyor", "Conveyor");
+conveyor_buildable!(
+    BSlowConveyor,
+    ConveyorTier::Slow,
+    "slow_conveyor",
+    "Slow Conveyor"
+);
+conveyor_buildable!(
+    BFastConveyor,
+    ConveyorTier::Fast,
+    "fast_conveyor",
+    "Fast Conveyor"
+);
+
+/// Makes every conveyor next to any of `positions` figure out its links
+/// again. Called whenever something is built or destroyed, since conveyors
+/// link to other conveyors as well as to the containers of other buildings.
+pub fn relink_conveyors_near(positions: &[IsoPos], commands: &mut Commands, maps: &BuildingMaps) {
+    for &pos in positions {
+        for &axis in &[IsoAxis::A, IsoAxis::B, IsoAxis::C] {
+            for &offset in &[-1, 1] {
+                if let Some(&conveyor) = maps.conveyors.get(pos.offset_axis(axis, offset)) {
+                    commands.entity(conveyor).insert(SetupNeeded);
+                }
             }
         }
     }
 }
 
-#[derive(Component, Default)]
-struct ConveyorLogic {
-    upstream: Option<Entity>,
+#[derive(Component)]
+pub struct ConveyorLogic {
+    /// How many ticks it takes an item to move onto this conveyor.
+    duration: u8,
+    /// Every conveyor that feeds into this one, with the one directly behind
+    /// it first and any side feeds after.
+    upstreams: Vec<Entity>,
+    /// Which upstream gets the first chance to give us an item next, so that
+    /// merging belts take turns. Upstreams are always linked in the same
+    /// order, so this survives being linked again.
+    next_upstream: usize,
+    /// A container of another building right behind this conveyor, like a
+    /// machine output, which items can be taken out of.
+    source: Option<Entity>,
+    /// A container of another building right in front of this conveyor, like
+    /// a machine input, which items get pushed into.
+    sink: Option<Entity>,
+    /// The other end of an underground entrance or exit, if it has one.
+    underground_pair: Option<Entity>,
+    /// Counts down while an item is still moving onto this conveyor.
     incoming_timer: u8,
+    /// Counts down while an item is still moving off this conveyor. Items
+    /// move at the speed of wherever they are going, so this can last longer
+    /// or shorter than our own duration.
     outgoing_timer: u8,
+    /// The container of another building the last item was pushed into. It
+    /// stays blocked until `outgoing_timer` runs out and the item is all the
+    /// way in.
+    pushed_into: Option<Entity>,
 }
+
+impl ConveyorLogic {
+    fn new(tier: ConveyorTier) -> Self {
+        Self {
+            duration: tier.duration(),
+            upstreams: Vec::new(),
+            next_upstream: 0,
+            source: None,
+            sink: None,
+            underground_pair: None,
+            incoming_timer: 0,
+            outgoing_timer: 0,
+            pushed_into: None,
+        }
+    }
+
+    pub fn incoming_timer(&self) -> u8 {
+        self.incoming_timer
+    }
+
+    pub fn outgoing_timer(&self) -> u8 {
+        self.outgoing_timer
+    }
+
+    pub fn next_upstream(&self) -> usize {
+        self.next_upstream
+    }
+
+    pub fn pushed_into(&self) -> Option<Entity> {
+        self.pushed_into
+    }
+
+    /// Puts the conveyor back into a previously saved state.
+    pub fn restore(
+        &mut self,
+        incoming_timer: u8,
+        outgoing_timer: u8,
+        next_upstream: usize,
+        pushed_into: Option<Entity>,
+    ) {
+        self.incoming_timer = incoming_timer;
+        self.outgoing_timer = outgoing_timer;
+        self.next_upstream = next_upstream;
+        self.pushed_into = pushed_into;
+    }
+}
+
 /// Conveyors that do not have any downstream.
 #[derive(Component)]
 struct TailConveyor;
-/// It takes this many ticks for an item to ride one unit of a conveyor.
-const DURATION: u8 = 4;
+
+/// How a conveyor trades items with the conveyors around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Link {
+    /// Takes items from whatever points into it, and has them taken by
+    /// whatever it points into.
+    Belt,
+    /// Pushes items into its outputs instead of having them taken, like a
+    /// splitter or a sorter.
+    Splitter,
+    /// Sends items to an exit this many cells ahead instead of having them
+    /// taken.
+    Entrance(u8),
+    /// Only takes items from an entrance behind it.
+    Exit,
+}
+
+impl Link {
+    fn new(
+        splitter: Option<&SplitterLogic>,
+        sorter: Option<&SorterLogic>,
+        entrance: Option<&UndergroundEntrance>,
+        exit: Option<&UndergroundExit>,
+    ) -> Self {
+        if splitter.is_some() || sorter.is_some() {
+            Self::Splitter
+        } else if let Some(entrance) = entrance {
+            Self::Entrance(entrance.length)
+        } else if exit.is_some() {
+            Self::Exit
+        } else {
+            Self::Belt
+        }
+    }
+
+    /// Whether the conveyor it points into should take items from it.
+    fn feeds_downstream(self) -> bool {
+        matches!(self, Self::Belt | Self::Exit)
+    }
+
+    /// Whether it takes items from the conveyors pointing into it.
+    fn takes_from_upstream(self) -> bool {
+        self != Self::Exit
+    }
+}
 
 fn setup(
     mut commands: Commands,
-    all_conveyors: Query<(Entity, &IsoPos, &IsoDirection), With<ConveyorLogic>>,
+    conveyor_map: Res<ConveyorMap>,
+    container_map: Res<ItemContainerMap>,
+    output_containers: Query<(), With<OutputContainer>>,
+    all_conveyors: Query<
+        (
+            &IsoDirection,
+            Option<&SplitterLogic>,
+            Option<&SorterLogic>,
+            Option<&UndergroundEntrance>,
+            Option<&UndergroundExit>,
+        ),
+        With<ConveyorLogic>,
+    >,
     mut unlinked_conveyors: Query<
         (Entity, &mut ConveyorLogic, &IsoPos, &IsoDirection),
         With<SetupNeeded>,
     >,
 ) {
+    // Looks up the conveyor at a position along with the direction it faces
+    // and how it links to others.
+    let conveyor_at = |pos: IsoPos| {
+        let id = *conveyor_map.get(pos)?;
+        let (&facing, splitter, sorter, entrance, exit) = all_conveyors.get(id).ok()?;
+        Some((id, facing, Link::new(splitter, sorter, entrance, exit)))
+    };
+    // Looks up the container at a position if it does not belong to a
+    // conveyor.
+    let container_at = |pos: IsoPos| {
+        if conveyor_map.get(pos).is_some() {
+            None
+        } else {
+            container_map.get(pos).copied()
+        }
+    };
     let mut check_has_setup_needed = Vec::new();
     for (id, mut conveyor, pos, facing) in unlinked_conveyors.iter_mut() {
+        let (_, _, link) = conveyor_at(*pos).unwrap();
         let upstream_pos = pos.offset_direction(*facing, -1);
         let downstream_pos = pos.offset_direction(*facing, 1);
         let mut has_downstream = false;
-        conveyor.upstream = None;
-        for (cid, cpos, cfacing) in all_conveyors.iter() {
-            // If they are in our upstream position and we are in their downstream
-            // position...
-            if *cpos == upstream_pos {
-                let candidate_downstream_pos = cpos.offset_direction(*cfacing, 1);
-                if candidate_downstream_pos == *pos {
-                    conveyor.upstream = Some(cid);
+        conveyor.upstreams.clear();
+        let previous_pair = conveyor.underground_pair.take();
+        let side_positions = pos
+            .surroundings()
+            .into_iter()
+            .filter(|&side| side != upstream_pos && side != downstream_pos);
+        // If they are next to us (but not in our downstream position) and we
+        // are in their downstream position...
+        let feed_positions = std::iter::once(upstream_pos)
+            .chain(side_positions)
+            .filter(|_| link.takes_from_upstream());
+        for feed_pos in feed_positions {
+            if let Some((cid, cfacing, clink)) = conveyor_at(feed_pos) {
+                if clink.feeds_downstream() && feed_pos.offset_direction(cfacing, 1) == *pos {
+                    conveyor.upstreams.push(cid);
                     // They have a downstream now, they cannot be
                     commands.entity(cid).remove::<TailConveyor>();
                 }
             }
-            // If they are in our downstream position and we are in their upstream
-            // position...
-            if *cpos == downstream_pos {
-                check_has_setup_needed.push(cid);
-                let candidate_upstream_pos = cpos.offset_direction(*cfacing, -1);
-                if candidate_upstream_pos == *pos {
+        }
+        if conveyor.next_upstream >= conveyor.upstreams.len() {
+            conveyor.next_upstream = 0;
+        }
+        if let Link::Entrance(length) = link {
+            // Our downstream is the exit at the other end of the tunnel, and
+            // whatever is right in front of us gets skipped.
+            let exit_pos = pos.offset_direction(*facing, length as i32);
+            if let Some((cid, cfacing, Link::Exit)) = conveyor_at(exit_pos) {
+                if cfacing == *facing {
+                    check_has_setup_needed.push(cid);
+                    conveyor.underground_pair = Some(cid);
                     has_downstream = true;
                 }
             }
+        } else if let Some((cid, cfacing, clink)) = conveyor_at(downstream_pos) {
+            // If they are in our downstream position and we are not in their
+            // downstream position...
+            check_has_setup_needed.push(cid);
+            if clink.takes_from_upstream() && downstream_pos.offset_direction(cfacing, 1) != *pos {
+                has_downstream = true;
+            }
+        }
+        if link == Link::Exit {
+            // The closest entrance facing the same way whose tunnel ends here.
+            for distance in 1..=MAX_UNDERGROUND_LENGTH {
+                let entrance_pos = pos.offset_direction(*facing, -(distance as i32));
+                if let Some((cid, cfacing, Link::Entrance(length))) = conveyor_at(entrance_pos) {
+                    if cfacing == *facing && length == distance {
+                        conveyor.underground_pair = Some(cid);
+                        commands.entity(cid).remove::<TailConveyor>();
+                        // The entrance is too far away to have been relinked
+                        // when we were built, so it does not know about us
+                        // yet. Once it does, it relinks us in turn, which is
+                        // when this stops.
+                        if previous_pair != Some(cid) {
+                            check_has_setup_needed.push(cid);
+                        }
+                        break;
+                    }
+                }
+            }
         }
+        // Only take from containers meant to be taken from, and never put
+        // things into them.
+        conveyor.source = container_at(upstream_pos)
+            .filter(|&c| link != Link::Exit && output_containers.get(c).is_ok());
+        conveyor.sink = container_at(downstream_pos)
+            .filter(|&c| link.feeds_downstream() && output_containers.get(c).is_err());
         commands.entity(id).remove::<SetupNeeded>();
-        if !has_downstream {
+        // Splitters and sorters push items into their outputs instead of
+        // having them pulled out, so they always start a tree of their own.
+        if !has_downstream || link == Link::Splitter {
             commands.entity(id).insert(TailConveyor);
         }
     }
@@ -142,45 +451,318 @@ fn setup(
 }
 
 fn tick(
-    tail_conveyors: Query<(Entity,), With<TailConveyor>>,
+    tick_clock: Res<TickClock>,
+    conveyor_map: Res<ConveyorMap>,
+    container_map: Res<ItemContainerMap>,
+    output_containers: Query<(), With<OutputContainer>>,
+    tail_conveyors: Query<
+        (Entity,),
+        (
+            With<TailConveyor>,
+            Without<SplitterLogic>,
+            Without<SorterLogic>,
+        ),
+    >,
+    facings: Query<(Entity, &IsoPos, &IsoDirection), With<ConveyorLogic>>,
+    mut splitters: Query<(Entity, &IsoPos, &IsoDirection, &mut SplitterLogic)>,
+    sorters: Query<(Entity, &IsoPos, &IsoDirection, &SorterLogic)>,
+    items: Query<&Item>,
     mut all_conveyors: Query<(&IsoPos, &mut ConveyorLogic, &mut ItemContainer)>,
+    mut others: Query<(&IsoPos, &mut ItemContainer), Without<ConveyorLogic>>,
+    mut tunnels: Query<&mut UndergroundEntrance>,
     mut all_items: Query<&mut ItemAnimator>,
 ) {
+    let mut visited = HashSet::new();
     for (current,) in tail_conveyors.iter() {
-        tick_conveyor(&mut all_conveyors, current, &mut all_items);
+        tick_tree(
+            &mut all_conveyors,
+            &mut others,
+            &mut tunnels,
+            current,
+            &mut all_items,
+            &tick_clock,
+            &mut visited,
+        );
+    }
+    // Splitters go after everything else so that their outputs have already
+    // been ticked when items get pushed into them, the same as if the outputs
+    // had pulled the items themselves.
+    let mut splitter_ids: Vec<(IsoPos, Entity)> = splitters
+        .iter_mut()
+        .map(|(id, &pos, _, _)| (pos, id))
+        .collect();
+    splitter_ids.sort();
+    for (_, id) in splitter_ids {
+        let (_, &pos, &facing, mut logic) = splitters.get_mut(id).unwrap();
+        let outputs = splitter::outputs(pos, facing, &conveyor_map, &facings);
+        logic.push_item(
+            id,
+            &outputs,
+            &mut all_conveyors,
+            &mut all_items,
+            &tick_clock,
+        );
+        tick_tree(
+            &mut all_conveyors,
+            &mut others,
+            &mut tunnels,
+            id,
+            &mut all_items,
+            &tick_clock,
+            &mut visited,
+        );
+    }
+    // Sorters go last for the same reason.
+    let mut sorter_ids: Vec<(IsoPos, Entity)> =
+        sorters.iter().map(|(id, &pos, _, _)| (pos, id)).collect();
+    sorter_ids.sort();
+    for (_, id) in sorter_ids {
+        let (_, &pos, &facing, logic) = sorters.get(id).unwrap();
+        let outputs = sorter::outputs(
+            pos,
+            facing,
+            &conveyor_map,
+            &container_map,
+            &facings,
+            &output_containers,
+        );
+        logic.push_item(
+            id,
+            &outputs,
+            &mut all_conveyors,
+            &mut others,
+            &items,
+            &mut all_items,
+            &tick_clock,
+        );
+        tick_tree(
+            &mut all_conveyors,
+            &mut others,
+            &mut tunnels,
+            id,
+            &mut all_items,
+            &tick_clock,
+            &mut visited,
+        );
+    }
+    // Anything left over must be part of or feeding into a closed loop, since
+    // every belt that ends somewhere ends at a tail.
+    let mut leftovers: Vec<(IsoPos, Entity)> = facings
+        .iter()
+        .filter(|(id, _, _)| !visited.contains(id))
+        .map(|(id, &pos, _)| (pos, id))
+        .collect();
+    leftovers.sort();
+    for (_, candidate) in leftovers {
+        if visited.contains(&candidate) {
+            continue;
+        }
+        let downstream_of = |id: Entity| {
+            // Entrances lead to the exit at the far end of their tunnel.
+            if tunnels.get_mut(id).is_ok() {
+                return all_conveyors.get_mut(id).ok()?.1.underground_pair;
+            }
+            let (_, pos, facing) = facings.get(id).ok()?;
+            let &next = conveyor_map.get(pos.offset_direction(*facing, 1))?;
+            let (_, next_conveyor, _) = all_conveyors.get_mut(next).ok()?;
+            next_conveyor.upstreams.contains(&id).then(|| next)
+        };
+        if let Some(start) = loop_start(candidate, downstream_of, |id| *facings.get(id).unwrap().1)
+        {
+            tick_tree(
+                &mut all_conveyors,
+                &mut others,
+                &mut tunnels,
+                start,
+                &mut all_items,
+                &tick_clock,
+                &mut visited,
+            );
+        }
     }
 }
 
+/// Follows the belt downstream from `member` until it runs in a circle, then
+/// returns the conveyor in that circle with the lowest position so that loops
+/// always start ticking from the same place.
+fn loop_start(
+    member: Entity,
+    mut downstream_of: impl FnMut(Entity) -> Option<Entity>,
+    position_of: impl Fn(Entity) -> IsoPos,
+) -> Option<Entity> {
+    let mut path = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = member;
+    while seen.insert(current) {
+        path.push(current);
+        current = downstream_of(current)?;
+    }
+    let loop_begin = path.iter().position(|&id| id == current).unwrap();
+    path[loop_begin..]
+        .iter()
+        .copied()
+        .min_by_key(|&id| (position_of(id), id))
+}
+
+/// Ticks `start` and then everything upstream of it, one conveyor at a time
+/// so that belts can be as long as we like.
+fn tick_tree(
+    all_conveyors: &mut Query<(&IsoPos, &mut ConveyorLogic, &mut ItemContainer)>,
+    others: &mut Query<(&IsoPos, &mut ItemContainer), Without<ConveyorLogic>>,
+    tunnels: &mut Query<&mut UndergroundEntrance>,
+    start: Entity,
+    all_items: &mut Query<&mut ItemAnimator>,
+    tick_clock: &TickClock,
+    visited: &mut HashSet<Entity>,
+) {
+    let mut pending = vec![start];
+    while let Some(current) = pending.pop() {
+        // In a loop, the conveyor we started from has already been ticked.
+        if !visited.insert(current) {
+            continue;
+        }
+        let upstreams = tick_conveyor(
+            all_conveyors,
+            others,
+            tunnels,
+            current,
+            all_items,
+            tick_clock,
+        );
+        // Reversed so that the upstreams get ticked in order.
+        pending.extend(upstreams.into_iter().rev());
+    }
+}
+
+/// Returns the conveyors upstream of this one, which should be ticked next.
+/// For an underground exit that includes the entrance of its tunnel.
 fn tick_conveyor(
     all_conveyors: &mut Query<(&IsoPos, &mut ConveyorLogic, &mut ItemContainer)>,
+    others: &mut Query<(&IsoPos, &mut ItemContainer), Without<ConveyorLogic>>,
+    tunnels: &mut Query<&mut UndergroundEntrance>,
     current: Entity,
     all_items: &mut Query<&mut ItemAnimator>,
-) {
+    tick_clock: &TickClock,
+) -> Vec<Entity> {
     let (pos, mut conveyor, mut item_container) = all_conveyors.get_mut(current).unwrap();
+    if let Some(sink) = conveyor.sink {
+        if let Ok((&sink_pos, mut sink_container)) = others.get_mut(sink) {
+            let can_push = !item_container.blocked()
+                && sink_container.item().is_none()
+                && !sink_container.blocked();
+            if let Some(item) = item_container.item().filter(|_| can_push) {
+                item_container.try_take();
+                let alignment = sink_container.alignment();
+                let mut animator = all_items.get_mut(item).unwrap();
+                animator.anim_to_container(sink_pos, alignment, conveyor.duration, tick_clock);
+                sink_container.put_item(item);
+                sink_container.set_blocked(true);
+                conveyor.outgoing_timer = conveyor.duration;
+                conveyor.pushed_into = Some(sink);
+            }
+        }
+    }
+    let tunnel_from = match tunnels.get_mut(current) {
+        // Underground entrances send their items on to the exit instead.
+        Ok(mut tunnel) => {
+            let can_push = conveyor.underground_pair.is_some() && !item_container.blocked();
+            if let Some(item) = item_container.item().filter(|_| can_push) {
+                if tunnel.try_push(item, conveyor.duration, tick_clock) {
+                    item_container.try_take();
+                    all_items.get_mut(item).unwrap().anim_hidden();
+                }
+            }
+            None
+        }
+        // Underground exits take items from the entrance at the other end.
+        Err(_) => conveyor.underground_pair,
+    };
     let empty = item_container.item().is_none();
     // True if the downstream belt could have taken an item we have but didn't.
     let not_taken = conveyor.incoming_timer == 0;
     conveyor.incoming_timer = conveyor.incoming_timer.saturating_sub(1);
     conveyor.outgoing_timer = conveyor.outgoing_timer.saturating_sub(1);
+    // Whatever we pushed into another building has finished moving in, so
+    // the building can have it now.
+    if conveyor.outgoing_timer == 0 {
+        let pushed_into = conveyor.pushed_into.take();
+        if let Some((_, mut container)) = pushed_into.and_then(|id| others.get_mut(id).ok()) {
+            container.set_blocked(false);
+        }
+    }
     let alignment = item_container.alignment();
-    let upstream = if let Some(upstream) = conveyor.upstream {
-        upstream
-    } else {
-        return;
-    };
+    if conveyor.upstreams.is_empty() && conveyor.source.is_none() && tunnel_from.is_none() {
+        return vec![];
+    }
+    let upstreams = conveyor.upstreams.clone();
+    let first_upstream = conveyor.next_upstream;
+    let source = conveyor.source;
+    let duration = conveyor.duration;
     item_container.set_blocked(false);
     if empty {
         let pos = pos.clone();
-        let (_, mut upstream, mut up_container) = all_conveyors.get_mut(upstream).unwrap();
-        if let Some(ientity) = up_container.try_take() {
-            let mut item = all_items.get_mut(ientity).unwrap();
-            item.anim_to_container(pos, alignment, DURATION);
-            upstream.outgoing_timer = DURATION;
+        // Offer each upstream a turn, starting with whoever has waited longest.
+        for offset in 0..upstreams.len() {
+            let index = (first_upstream + offset) % upstreams.len();
+            let (&up_pos, mut upstream, mut up_container) =
+                all_conveyors.get_mut(upstreams[index]).unwrap();
+            let turning = match (up_container.alignment(), alignment) {
+                (
+                    ItemContainerAlignment::AxisAligned(from),
+                    ItemContainerAlignment::AxisAligned(to),
+                ) => from != to,
+                _ => false,
+            };
+            if let Some(ientity) = up_container.try_take() {
+                // Items animate from wherever they were sitting on the feeding
+                // belt, so side feeds come in from the side and follow the
+                // bend instead of cutting across it.
+                let mut item = all_items.get_mut(ientity).unwrap();
+                if turning {
+                    item.anim_around_corner(up_pos, pos, alignment, duration, tick_clock);
+                } else {
+                    item.anim_to_container(pos, alignment, duration, tick_clock);
+                }
+                upstream.outgoing_timer = duration;
 
-            let (_, mut this, mut this_container) = all_conveyors.get_mut(current).unwrap();
-            this.incoming_timer = DURATION - 1;
-            this_container.put_item(ientity);
-            this_container.set_blocked(true);
+                let (_, mut this, mut this_container) = all_conveyors.get_mut(current).unwrap();
+                this.incoming_timer = duration - 1;
+                this.next_upstream = (index + 1) % upstreams.len();
+                this_container.put_item(ientity);
+                this_container.set_blocked(true);
+                break;
+            }
+        }
+        // Other buildings only get a turn once no belt has anything to give.
+        let (_, _, this_container) = all_conveyors.get_mut(current).unwrap();
+        let still_empty = this_container.item().is_none();
+        let source = source
+            .filter(|_| still_empty)
+            .and_then(|source| others.get_mut(source).ok());
+        if let Some((_, mut source_container)) = source {
+            if let Some(ientity) = source_container.try_take() {
+                let mut item = all_items.get_mut(ientity).unwrap();
+                item.anim_to_container(pos, alignment, duration, tick_clock);
+
+                let (_, mut this, mut this_container) = all_conveyors.get_mut(current).unwrap();
+                this.incoming_timer = duration - 1;
+                this_container.put_item(ientity);
+                this_container.set_blocked(true);
+            }
+        }
+        // Items come back up in the middle of the exit, having been hidden the
+        // whole way there.
+        let tunnel = tunnel_from.and_then(|entrance| tunnels.get_mut(entrance).ok());
+        if let Some(mut tunnel) = tunnel {
+            if let Some(ientity) = tunnel.pop_arrived(tick_clock) {
+                let mut item = all_items.get_mut(ientity).unwrap();
+                item.anim_stationary_in_container(pos, alignment);
+
+                let (_, mut this, mut this_container) = all_conveyors.get_mut(current).unwrap();
+                this.incoming_timer = duration - 1;
+                this_container.put_item(ientity);
+                this_container.set_blocked(true);
+            }
         }
     } else if not_taken {
         item_container.item().map(|e| {
@@ -194,7 +776,9 @@ fn tick_conveyor(
     // conveyor if there are items partially inside the conveyor.
     item_container.set_blocked(conveyor.incoming_timer > 0 || conveyor.outgoing_timer > 0);
 
-    tick_conveyor(all_conveyors, upstream, all_items);
+    let mut next = upstreams;
+    next.extend(tunnel_from);
+    next
 }
 
 pub struct Plug;
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use maplit::hashmap;
+use serde::{Deserialize, Serialize};
+
+use super::{ConveyorLogic, ConveyorTier};
+use crate::{
+    buildable::{
+        storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
+        BuildingMaps, WhichMap,
+    },
+    iso::ItemContainerMap,
+    item::{
+        ItemAnimator, ItemContainer, ItemContainerAlignment, ItemRule, OutputContainer,
+        ReferenceItem,
+    },
+    prelude::*,
+};
+
+/// Takes items from the conveyors feeding into it, sending the ones that
+/// pass its rule out of the front and everything else out of the side.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BSorter {
+    pub rule: ItemRule,
+}
+
+#[derive(Component)]
+pub(super) struct SorterLogic {
+    rule: ItemRule,
+}
+
+/// Somewhere a sorter can put an item.
+#[derive(Clone, Copy)]
+pub(super) enum Output {
+    Conveyor(Entity),
+    /// The container of another building, like a machine input.
+    Container(Entity),
+}
+
+pub(super) struct Outputs {
+    matching: Option<Output>,
+    rest: Option<Output>,
+}
+
+impl Buildable for BSorter {
+    type ExtraData = ();
+    const ID: &'static str = "sorter";
+    const NAME: &'static str = "Sorter";
+
+    fn details(
+        &self,
+        position: IsoPos,
+        _direction: IsoDirection,
+        _maps: &BuildingMaps,
+    ) -> Option<BuildingDetails> {
+        Some(BuildingDetails {
+            shape: vec![position],
+            maps: vec![
+                WhichMap::Buildings,
+                WhichMap::Conveyors,
+                WhichMap::ItemContainers,
+            ],
+            cost: ItemList::from_counts(hashmap![
+                ReferenceItem::IronLump.as_item() => 3,
+                ReferenceItem::PureAnimus.as_item() => 2,
+            ]),
+        })
+    }
+
+    fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+        ctx.commands
+            .insert(ConveyorLogic::new(ConveyorTier::Normal))
+            .insert(SorterLogic { rule: self.rule })
+            .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+            .insert(SetupNeeded);
+    }
+
+    fn spawn_extras(
+        &self,
+        _ctx: &mut BuildingContext,
+        _maps: &mut BuildingMaps,
+    ) -> (Vec<Entity>, Self::ExtraData) {
+        (vec![], ())
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        ConveyorTier::Normal.spawn_art(ctx)
+    }
+
+    fn display_name(&self) -> String {
+        format!("{} ({})", Self::NAME, self.rule)
+    }
+}
+
+/// Finds where the sorter at `pos` sends items that pass its rule and where
+/// it sends the rest. Either can be a conveyor that does not feed into the
+/// sorter or the container of another building that items can be put into.
+pub(super) fn outputs(
+    pos: IsoPos,
+    facing: IsoDirection,
+    conveyor_map: &ConveyorMap,
+    container_map: &ItemContainerMap,
+    facings: &Query<(Entity, &IsoPos, &IsoDirection), With<ConveyorLogic>>,
+    output_containers: &Query<(), With<OutputContainer>>,
+) -> Outputs {
+    let front_pos = pos.offset_direction(facing, 1);
+    let back_pos = pos.offset_direction(facing, -1);
+    let side_pos = pos
+        .surroundings()
+        .into_iter()
+        .find(|&side| side != front_pos && side != back_pos);
+    let output_at = |out_pos: IsoPos| {
+        if let Some(&id) = conveyor_map.get(out_pos) {
+            let (_, _, out_facing) = facings.get(id).ok()?;
+            // Conveyors pointing into the sorter are inputs instead.
+            (out_pos.offset_direction(*out_facing, 1) != pos).then(|| Output::Conveyor(id))
+        } else {
+            let &id = container_map.get(out_pos)?;
+            // Containers meant to be taken from never get anything put in.
+            output_containers
+                .get(id)
+                .is_err()
+                .then(|| Output::Container(id))
+        }
+    };
+    Outputs {
+        matching: output_at(front_pos),
+        rest: side_pos.and_then(output_at),
+    }
+}
+
+impl SorterLogic {
+    /// Moves the item in the sorter into the output its rule picks. If that
+    /// output is full the item waits, even if the other output is free.
+    pub(super) fn push_item(
+        &self,
+        this: Entity,
+        outputs: &Outputs,
+        all_conveyors: &mut Query<(&IsoPos, &mut ConveyorLogic, &mut ItemContainer)>,
+        others: &mut Query<(&IsoPos, &mut ItemContainer), Without<ConveyorLogic>>,
+        items: &Query<&Item>,
+        all_items: &mut Query<&mut ItemAnimator>,
+        tick_clock: &TickClock,
+    ) {
+        let (item, duration) = {
+            let (_, logic, container) = all_conveyors.get_mut(this).unwrap();
+            match container.item() {
+                Some(item) if !container.blocked() => (item, logic.duration),
+                _ => return,
+            }
+        };
+        let output = if self.rule.matches(items.get(item).unwrap()) {
+            outputs.matching
+        } else {
+            outputs.rest
+        };
+        let mut pushed_into = None;
+        let duration = match output {
+            Some(Output::Conveyor(id)) => {
+                let (&out_pos, mut out_logic, mut out_container) =
+                    all_conveyors.get_mut(id).unwrap();
+                if out_container.item().is_some() || out_container.blocked() {
+                    return;
+                }
+                let alignment = out_container.alignment();
+                let duration = out_logic.duration;
+                let mut animator = all_items.get_mut(item).unwrap();
+                animator.anim_to_container(out_pos, alignment, duration, tick_clock);
+                out_logic.incoming_timer = duration - 1;
+                out_container.put_item(item);
+                out_container.set_blocked(true);
+                duration
+            }
+            Some(Output::Container(id)) => {
+                let (&out_pos, mut out_container) = match others.get_mut(id) {
+                    Ok(output) => output,
+                    Err(_) => return,
+                };
+                if out_container.item().is_some() || out_container.blocked() {
+                    return;
+                }
+                let alignment = out_container.alignment();
+                let mut animator = all_items.get_mut(item).unwrap();
+                animator.anim_to_container(out_pos, alignment, duration, tick_clock);
+                out_container.put_item(item);
+                out_container.set_blocked(true);
+                pushed_into = Some(id);
+                duration
+            }
+            None => return,
+        };
+        let (_, mut this_logic, mut this_container) = all_conveyors.get_mut(this).unwrap();
+        this_container.try_take();
+        this_logic.outgoing_timer = duration;
+        this_logic.pushed_into = pushed_into;
+    }
+}
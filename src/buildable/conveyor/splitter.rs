@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use maplit::hashmap;
+use serde::{Deserialize, Serialize};
+
+use super::{ConveyorLogic, ConveyorTier};
+use crate::{
+    buildable::{
+        storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
+        BuildingMaps, WhichMap,
+    },
+    item::{ItemAnimator, ItemContainer, ItemContainerAlignment, ReferenceItem},
+    prelude::*,
+};
+
+/// Takes items from the conveyors feeding into it and hands them out in turn
+/// to every neighbouring conveyor that starts at it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BSplitter {
+    /// When set, the conveyor in front of the splitter gets every item it can
+    /// take and the other outputs only get what it can't.
+    pub prioritize_front: bool,
+}
+
+#[derive(Component)]
+pub struct SplitterLogic {
+    prioritize_front: bool,
+    /// Which output gets the first chance at the next item.
+    next_output: usize,
+}
+
+/// The conveyors a splitter can push items into.
+pub(super) struct Outputs {
+    front: Option<Entity>,
+    /// Includes `front`, which always comes first.
+    all: Vec<Entity>,
+}
+
+impl Buildable for BSplitter {
+    type ExtraData = ();
+    const ID: &'static str = "splitter";
+    const NAME: &'static str = "Splitter";
+
+    fn details(
+        &self,
+        position: IsoPos,
+        _direction: IsoDirection,
+        _maps: &BuildingMaps,
+    ) -> Option<BuildingDetails> {
+        Some(BuildingDetails {
+            shape: vec![position],
+            maps: vec![
+                WhichMap::Buildings,
+                WhichMap::Conveyors,
+                WhichMap::ItemContainers,
+            ],
+            cost: ItemList::from_counts(hashmap![
+                ReferenceItem::IronLump.as_item() => 2,
+                ReferenceItem::PureAnimus.as_item() => 1,
+            ]),
+        })
+    }
+
+    fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+        ctx.commands
+            .insert(ConveyorLogic::new(ConveyorTier::Normal))
+            .insert(SplitterLogic {
+                prioritize_front: self.prioritize_front,
+                next_output: 0,
+            })
+            .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+            .insert(SetupNeeded);
+    }
+
+    fn spawn_extras(
+        &self,
+        _ctx: &mut BuildingContext,
+        _maps: &mut BuildingMaps,
+    ) -> (Vec<Entity>, Self::ExtraData) {
+        (vec![], ())
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        ConveyorTier::Normal.spawn_art(ctx)
+    }
+
+    fn display_name(&self) -> String {
+        if self.prioritize_front {
+            format!("{} (Front First)", Self::NAME)
+        } else {
+            Self::NAME.to_owned()
+        }
+    }
+}
+
+/// Finds every conveyor next to the splitter at `pos` that it could push
+/// items into. Only conveyors leading away from the splitter count, so belts
+/// that run past it or into it are left alone.
+pub(super) fn outputs(
+    pos: IsoPos,
+    facing: IsoDirection,
+    conveyor_map: &ConveyorMap,
+    facings: &Query<(Entity, &IsoPos, &IsoDirection), With<ConveyorLogic>>,
+) -> Outputs {
+    let front_pos = pos.offset_direction(facing, 1);
+    let back_pos = pos.offset_direction(facing, -1);
+    let side_positions = pos
+        .surroundings()
+        .into_iter()
+        .filter(|&side| side != front_pos && side != back_pos);
+    let mut result = Outputs {
+        front: None,
+        all: Vec::new(),
+    };
+    for out_pos in std::iter::once(front_pos).chain(side_positions) {
+        let output = conveyor_map
+            .get(out_pos)
+            .and_then(|&id| facings.get(id).ok())
+            .filter(|(_, _, out_facing)| out_pos.offset_direction(**out_facing, -1) == pos);
+        if let Some((id, _, _)) = output {
+            if out_pos == front_pos {
+                result.front = Some(id);
+            }
+            result.all.push(id);
+        }
+    }
+    result
+}
+
+impl SplitterLogic {
+    /// Moves the item in the splitter into the next output that can take it,
+    /// skipping any that are full or blocked.
+    pub(super) fn push_item(
+        &mut self,
+        this: Entity,
+        outputs: &Outputs,
+        all_conveyors: &mut Query<(&IsoPos, &mut ConveyorLogic, &mut ItemContainer)>,
+        all_items: &mut Query<&mut ItemAnimator>,
+        tick_clock: &TickClock,
+    ) {
+        let item = {
+            let (_, _, container) = all_conveyors.get_mut(this).unwrap();
+            match container.item() {
+                Some(item) if !container.blocked() => item,
+                _ => return,
+            }
+        };
+        let count = outputs.all.len();
+        let mut order: Vec<usize> = (0..count)
+            .map(|offset| (self.next_output + offset) % count)
+            .collect();
+        let prioritized = self.prioritize_front && outputs.front.is_some();
+        if prioritized {
+            order.retain(|&index| index != 0);
+            order.insert(0, 0);
+        }
+        for index in order {
+            let (&out_pos, mut out_logic, mut out_container) =
+                all_conveyors.get_mut(outputs.all[index]).unwrap();
+            if out_container.item().is_some() || out_container.blocked() {
+                continue;
+            }
+            let alignment = out_container.alignment();
+            let mut animator = all_items.get_mut(item).unwrap();
+            let duration = out_logic.duration;
+            animator.anim_to_container(out_pos, alignment, duration, tick_clock);
+            out_logic.incoming_timer = duration - 1;
+            out_container.put_item(item);
+            out_container.set_blocked(true);
+
+            let (_, mut this_logic, mut this_container) = all_conveyors.get_mut(this).unwrap();
+            this_container.try_take();
+            this_logic.outgoing_timer = duration;
+            // The front output getting its priority does not use up a turn.
+            if !(prioritized && index == 0) {
+                self.next_output = (index + 1) % count;
+            }
+            return;
+        }
+    }
+
+    pub fn next_output(&self) -> usize {
+        self.next_output
+    }
+
+    /// Puts the splitter back into a previously saved state.
+    pub fn restore(&mut self, next_output: usize) {
+        self.next_output = next_output;
+    }
+}
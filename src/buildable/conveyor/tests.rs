@@ -0,0 +1,617 @@
+#![cfg(test)]
+
+use bevy::ecs::system::SystemState;
+
+use super::*;
+use crate::{
+    buildable::{destroyer::BDestroyer, spawner::BSpawner, stats::ThroughputStats},
+    headless::{app, build, destroy, run_ticks},
+    iso::ItemContainerMap,
+    item::{Element, ItemRule},
+};
+
+const ALL_DIRECTIONS: [IsoDirection; 6] = [
+    IsoDirection::PosA,
+    IsoDirection::NegC,
+    IsoDirection::PosB,
+    IsoDirection::NegA,
+    IsoDirection::PosC,
+    IsoDirection::NegB,
+];
+
+/// Places `length` conveyors in a straight line, all in the same frame.
+fn build_line(app: &mut App, start: IsoPos, direction: IsoDirection, length: usize) {
+    let mut pos = start;
+    for _ in 0..length {
+        build(app, BConveyor, pos, direction);
+        pos = pos.offset_direction(direction, 1);
+    }
+}
+
+#[test]
+fn links_long_line() {
+    let mut app = app();
+    let length = 2000;
+    build_line(&mut app, IsoPos::origin(), IsoDirection::PosA, length);
+    run_ticks(&mut app, 2);
+    let mut conveyors = app
+        .world
+        .query::<(&ConveyorLogic, Option<&TailConveyor>, Option<&SetupNeeded>)>();
+    let mut linked = 0;
+    let mut tails = 0;
+    for (conveyor, tail, setup_needed) in conveyors.iter(&app.world) {
+        assert!(setup_needed.is_none());
+        linked += conveyor.upstreams.len();
+        tails += tail.is_some() as usize;
+    }
+    assert_eq!(linked, length - 1);
+    assert_eq!(tails, 1);
+}
+
+#[test]
+fn ticks_very_long_belt() {
+    let mut app = app();
+    let length = 100_000;
+    build_line(&mut app, IsoPos::origin(), IsoDirection::PosA, length);
+    run_ticks(&mut app, 1);
+    let head = IsoPos::origin();
+    put_items(&mut app, &[head], ReferenceItem::Magnetite);
+    run_ticks(&mut app, ConveyorTier::Normal.duration() as u32 * 3);
+    // The item has left the first conveyor and is still on the belt.
+    let (now, items) = occupied(&mut app);
+    assert_eq!(items.len(), 1);
+    assert_ne!(now, vec![head]);
+}
+
+/// Places six conveyors around a vertex so that each one feeds the next.
+fn build_loop(app: &mut App, start: IsoPos) -> Vec<IsoPos> {
+    let mut positions = Vec::new();
+    let mut pos = start;
+    let mut direction = IsoDirection::PosA;
+    for _ in 0..6 {
+        build(app, BConveyor, pos, direction);
+        positions.push(pos);
+        pos = pos.offset_direction(direction, 1);
+        direction = direction.counter_clockwise();
+    }
+    assert_eq!(pos, start);
+    positions
+}
+
+/// Puts an item into each of the containers at `positions` which can take one.
+fn put_items(app: &mut App, positions: &[IsoPos], of: ReferenceItem) {
+    let mut state: SystemState<(
+        Commands,
+        Res<CommonAssets>,
+        Res<ItemContainerMap>,
+        Query<&mut ItemContainer>,
+    )> = SystemState::new(&mut app.world);
+    let (mut commands, common_assets, container_map, mut containers) =
+        state.get_mut(&mut app.world);
+    for &pos in positions {
+        let &container = container_map.get(pos).unwrap();
+        let mut container = containers.get_mut(container).unwrap();
+        if container.item().is_none() && !container.blocked() {
+            container.create_and_put_item(&mut commands, &common_assets, pos, of.as_item());
+        }
+    }
+    state.apply(&mut app.world);
+}
+
+/// Returns the positions of every conveyor holding an item, along with the
+/// items themselves.
+fn occupied(app: &mut App) -> (Vec<IsoPos>, HashSet<Entity>) {
+    let mut containers = app.world.query::<(&IsoPos, &ItemContainer)>();
+    let mut positions = Vec::new();
+    let mut items = HashSet::new();
+    for (&pos, container) in containers.iter(&app.world) {
+        if let Some(item) = container.item() {
+            positions.push(pos);
+            items.insert(item);
+        }
+    }
+    positions.sort();
+    (positions, items)
+}
+
+/// Counts how many items of a kind are sitting in containers whose position
+/// passes `filter`.
+fn count_items(app: &mut App, of: ReferenceItem, filter: impl Fn(IsoPos) -> bool) -> usize {
+    let mut containers = app.world.query::<(&IsoPos, &ItemContainer)>();
+    let mut items = app.world.query::<&Item>();
+    let mut count = 0;
+    for (&pos, container) in containers.iter(&app.world) {
+        if let Some(item) = container.item() {
+            if filter(pos) && *items.get(&app.world, item).unwrap() == of.as_item() {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn closed_loop_moves_items() {
+    let mut app = app();
+    let positions = build_loop(&mut app, IsoPos::origin());
+    run_ticks(&mut app, 1);
+    let mut conveyors = app.world.query::<&TailConveyor>();
+    assert_eq!(conveyors.iter(&app.world).count(), 0);
+
+    put_items(
+        &mut app,
+        &[positions[0], positions[2], positions[4]],
+        ReferenceItem::Magnetite,
+    );
+    let (start, _) = occupied(&mut app);
+    let mut moved = false;
+    for _ in 0..24 {
+        run_ticks(&mut app, 1);
+        let (now, items) = occupied(&mut app);
+        // No item was duplicated or lost.
+        assert_eq!(now.len(), 3);
+        assert_eq!(items.len(), 3);
+        moved |= now != start;
+    }
+    assert!(moved);
+    assert_eq!(app.world.query::<&Item>().iter(&app.world).count(), 3);
+}
+
+#[test]
+fn merge_alternates_between_feeds() {
+    let mut app = app();
+    let merge = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    build_line(&mut app, merge, direction, 40);
+    let straight = merge.offset_direction(direction, -1);
+    build(&mut app, BConveyor, straight, direction);
+    let side = merge
+        .surroundings()
+        .into_iter()
+        .find(|&pos| pos != straight && pos != merge.offset_direction(direction, 1))
+        .unwrap();
+    let side_direction = ALL_DIRECTIONS
+        .iter()
+        .copied()
+        .find(|&dir| side.offset_direction(dir, 1) == merge)
+        .unwrap();
+    build(&mut app, BConveyor, side, side_direction);
+    run_ticks(&mut app, 1);
+
+    for _ in 0..100 {
+        put_items(&mut app, &[straight], ReferenceItem::Magnetite);
+        put_items(&mut app, &[side], ReferenceItem::IronLump);
+        run_ticks(&mut app, 1);
+    }
+
+    let from_straight = count_items(&mut app, ReferenceItem::Magnetite, |pos| {
+        pos != straight && pos != side
+    });
+    let from_side = count_items(&mut app, ReferenceItem::IronLump, |pos| {
+        pos != straight && pos != side
+    });
+    assert!(from_straight > 0);
+    assert!((from_straight as i32 - from_side as i32).abs() <= 1);
+}
+
+/// Feeds a splitter at the origin facing +A from behind for 100 ticks, then
+/// returns how many items ended up in front of it and to its side.
+fn run_splitter(prioritize_front: bool) -> (usize, usize) {
+    let mut app = app();
+    let splitter = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let input = splitter.offset_direction(direction, -1);
+    let front = splitter.offset_direction(direction, 1);
+    let side = splitter
+        .surroundings()
+        .into_iter()
+        .find(|&pos| pos != input && pos != front)
+        .unwrap();
+    let side_direction = ALL_DIRECTIONS
+        .iter()
+        .copied()
+        .find(|&dir| side.offset_direction(dir, -1) == splitter)
+        .unwrap();
+    build(&mut app, BConveyor, input, direction);
+    build(
+        &mut app,
+        BSplitter { prioritize_front },
+        splitter,
+        direction,
+    );
+    build_line(&mut app, front, direction, 40);
+    build_line(&mut app, side, side_direction, 40);
+    run_ticks(&mut app, 1);
+
+    for _ in 0..100 {
+        put_items(&mut app, &[input], ReferenceItem::Magnetite);
+        run_ticks(&mut app, 1);
+    }
+    let front_line: HashSet<IsoPos> = (0..40)
+        .map(|i| front.offset_direction(direction, i))
+        .collect();
+    let side_line: HashSet<IsoPos> = (0..40)
+        .map(|i| side.offset_direction(side_direction, i))
+        .collect();
+    (
+        count_items(&mut app, ReferenceItem::Magnetite, |pos| {
+            front_line.contains(&pos)
+        }),
+        count_items(&mut app, ReferenceItem::Magnetite, |pos| {
+            side_line.contains(&pos)
+        }),
+    )
+}
+
+#[test]
+fn splitter_alternates_outputs() {
+    let (front, side) = run_splitter(false);
+    assert!(front > 0);
+    assert!((front as i32 - side as i32).abs() <= 1);
+}
+
+#[test]
+fn splitter_prioritizes_front() {
+    let (front, side) = run_splitter(true);
+    // The side only gets items while the front is still busy with the last
+    // one.
+    assert!(front > side);
+}
+
+#[test]
+fn splitter_ignores_belts_running_past() {
+    let mut app = app();
+    let splitter = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let input = splitter.offset_direction(direction, -1);
+    let front = splitter.offset_direction(direction, 1);
+    let side = splitter
+        .surroundings()
+        .into_iter()
+        .find(|&pos| pos != input && pos != front)
+        .unwrap();
+    let past_direction = ALL_DIRECTIONS
+        .iter()
+        .copied()
+        .find(|&dir| {
+            side.offset_direction(dir, 1) != splitter && side.offset_direction(dir, -1) != splitter
+        })
+        .unwrap();
+    build(&mut app, BConveyor, input, direction);
+    build(
+        &mut app,
+        BSplitter {
+            prioritize_front: false,
+        },
+        splitter,
+        direction,
+    );
+    build_line(&mut app, front, direction, 40);
+    build(&mut app, BConveyor, side, past_direction);
+    run_ticks(&mut app, 1);
+
+    for _ in 0..100 {
+        put_items(&mut app, &[input], ReferenceItem::Magnetite);
+        run_ticks(&mut app, 1);
+    }
+    let front_line: HashSet<IsoPos> = (0..40)
+        .map(|i| front.offset_direction(direction, i))
+        .collect();
+    let on_front = count_items(&mut app, ReferenceItem::Magnetite, |pos| {
+        front_line.contains(&pos)
+    });
+    let on_side = count_items(&mut app, ReferenceItem::Magnetite, |pos| pos == side);
+    assert!(on_front > 0);
+    assert_eq!(on_side, 0);
+}
+
+#[test]
+fn belt_runs_from_spawner_to_destroyer() {
+    let mut app = app();
+    let spawner = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let length = 6;
+    build(
+        &mut app,
+        BSpawner {
+            item: ReferenceItem::Magnetite,
+            interval: 8,
+        },
+        spawner,
+        direction,
+    );
+    build_line(
+        &mut app,
+        spawner.offset_direction(direction, 1),
+        direction,
+        length,
+    );
+    let destroyer = spawner.offset_direction(direction, length as i32 + 1);
+    build(&mut app, BDestroyer, destroyer, direction);
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let made = stats.get(spawner).unwrap().items_out;
+    let destroyed = stats.get(destroyer).unwrap().items_in;
+    assert!(destroyed > 0);
+    // Anything not destroyed yet is still on the belt or in the spawner.
+    let (on_belt, _) = occupied(&mut app);
+    assert_eq!(made, destroyed + on_belt.len() as u64);
+}
+
+#[test]
+fn sink_waits_for_items_to_arrive() {
+    let mut app = app();
+    let conveyor = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let destroyer = conveyor.offset_direction(direction, 1);
+    build(&mut app, BConveyor, conveyor, direction);
+    build(&mut app, BDestroyer, destroyer, direction);
+    run_ticks(&mut app, 1);
+    put_items(&mut app, &[conveyor], ReferenceItem::Magnetite);
+
+    let mut arrived_after = None;
+    for tick in 1..=20 {
+        run_ticks(&mut app, 1);
+        let mut containers = app.world.query::<(&IsoPos, &ItemContainer)>();
+        let (_, container) = containers
+            .iter(&app.world)
+            .find(|(&pos, _)| pos == destroyer)
+            .unwrap();
+        if container.item().is_some() && arrived_after.is_none() {
+            // Still on its way in, so the destroyer must leave it alone.
+            assert!(container.blocked());
+            arrived_after = Some(tick);
+        }
+        let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+        if stats.get(destroyer).unwrap().items_in > 0 {
+            let pushed = arrived_after.unwrap();
+            assert!(tick - pushed >= ConveyorTier::Normal.duration() as u32 - 1);
+            return;
+        }
+    }
+    panic!("The item was never destroyed");
+}
+
+fn build_tier(app: &mut App, tier: ConveyorTier, position: IsoPos, direction: IsoDirection) {
+    match tier {
+        ConveyorTier::Slow => build(app, BSlowConveyor, position, direction),
+        ConveyorTier::Normal => build(app, BConveyor, position, direction),
+        ConveyorTier::Fast => build(app, BFastConveyor, position, direction),
+    }
+}
+
+/// Returns how many ticks it takes an item put on the first of a line of
+/// conveyors to reach the last one.
+fn ride_time(tiers: &[ConveyorTier]) -> u32 {
+    let mut app = app();
+    let direction = IsoDirection::PosA;
+    let head = IsoPos::origin();
+    let mut pos = head;
+    for tier in tiers {
+        build_tier(&mut app, *tier, pos, direction);
+        pos = pos.offset_direction(direction, 1);
+    }
+    let tail = head.offset_direction(direction, tiers.len() as i32 - 1);
+    run_ticks(&mut app, 1);
+    put_items(&mut app, &[head], ReferenceItem::Magnetite);
+    for ticks in 1..1000 {
+        run_ticks(&mut app, 1);
+        let (now, items) = occupied(&mut app);
+        assert_eq!(items.len(), 1);
+        if now == vec![tail] {
+            return ticks;
+        }
+    }
+    panic!("The item never reached the end of the line");
+}
+
+#[test]
+fn tiers_move_at_their_own_speed() {
+    let slow = ride_time(&[ConveyorTier::Slow; 8]);
+    let normal = ride_time(&[ConveyorTier::Normal; 8]);
+    let fast = ride_time(&[ConveyorTier::Fast; 8]);
+    assert!(slow > normal);
+    assert!(normal > fast);
+}
+
+#[test]
+fn mixed_tiers_hand_items_over() {
+    let mut app = app();
+    let spawner = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let tiers = [
+        ConveyorTier::Fast,
+        ConveyorTier::Fast,
+        ConveyorTier::Slow,
+        ConveyorTier::Slow,
+        ConveyorTier::Normal,
+        ConveyorTier::Fast,
+        ConveyorTier::Slow,
+    ];
+    build(
+        &mut app,
+        BSpawner {
+            item: ReferenceItem::Magnetite,
+            interval: 2,
+        },
+        spawner,
+        direction,
+    );
+    let mut pos = spawner.offset_direction(direction, 1);
+    for tier in tiers.iter() {
+        build_tier(&mut app, *tier, pos, direction);
+        pos = pos.offset_direction(direction, 1);
+    }
+    let destroyer = pos;
+    build(&mut app, BDestroyer, destroyer, direction);
+    run_ticks(&mut app, 400);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let made = stats.get(spawner).unwrap().items_out;
+    let destroyed = stats.get(destroyer).unwrap().items_in;
+    assert!(destroyed > 0);
+    // Nothing gets through faster than the slowest belt allows.
+    assert!(destroyed <= 400 / ConveyorTier::Slow.duration() as u64);
+    let (on_belt, _) = occupied(&mut app);
+    assert_eq!(made, destroyed + on_belt.len() as u64);
+}
+
+#[test]
+fn underground_carries_items_past_buildings() {
+    let mut app = app();
+    let spawner = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let length = 4;
+    build(
+        &mut app,
+        BSpawner {
+            item: ReferenceItem::Magnetite,
+            interval: 4,
+        },
+        spawner,
+        direction,
+    );
+    build(
+        &mut app,
+        BConveyor,
+        spawner.offset_direction(direction, 1),
+        direction,
+    );
+    let entrance = spawner.offset_direction(direction, 2);
+    build(
+        &mut app,
+        BUndergroundEntrance { length },
+        entrance,
+        direction,
+    );
+    // Conveyors crossing the path of the tunnel should never see its items.
+    let crossing: Vec<_> = (1..length as i32)
+        .map(|distance| entrance.offset_direction(direction, distance))
+        .collect();
+    for &pos in &crossing {
+        build(&mut app, BConveyor, pos, IsoDirection::PosB);
+    }
+    let exit = entrance.offset_direction(direction, length as i32);
+    build(&mut app, BUndergroundExit, exit, direction);
+    build(
+        &mut app,
+        BConveyor,
+        exit.offset_direction(direction, 1),
+        direction,
+    );
+    let destroyer = exit.offset_direction(direction, 2);
+    build(&mut app, BDestroyer, destroyer, direction);
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let made = stats.get(spawner).unwrap().items_out;
+    let destroyed = stats.get(destroyer).unwrap().items_in;
+    assert!(destroyed > 0);
+    let (on_belt, _) = occupied(&mut app);
+    assert!(on_belt.iter().all(|pos| !crossing.contains(pos)));
+    // Whatever is not on a belt or destroyed is still underground.
+    let alive = app.world.query::<&Item>().iter(&app.world).count() as u64;
+    assert!(alive >= on_belt.len() as u64);
+    assert_eq!(made, destroyed + alive);
+}
+
+#[test]
+fn underground_exit_built_later_links_up() {
+    let mut app = app();
+    let spawner = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let length = 4;
+    build(
+        &mut app,
+        BSpawner {
+            item: ReferenceItem::Magnetite,
+            interval: 4,
+        },
+        spawner,
+        direction,
+    );
+    build(
+        &mut app,
+        BConveyor,
+        spawner.offset_direction(direction, 1),
+        direction,
+    );
+    let entrance = spawner.offset_direction(direction, 2);
+    build(
+        &mut app,
+        BUndergroundEntrance { length },
+        entrance,
+        direction,
+    );
+    run_ticks(&mut app, 10);
+    let exit = entrance.offset_direction(direction, length as i32);
+    build(&mut app, BUndergroundExit, exit, direction);
+    let destroyer = exit.offset_direction(direction, 1);
+    build(&mut app, BDestroyer, destroyer, direction);
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    assert!(stats.get(destroyer).unwrap().items_in > 0);
+
+    // Items still underground go away along with the entrance, so the only
+    // item left outside of a container is whatever the entrance was holding.
+    destroy(&mut app, entrance);
+    run_ticks(&mut app, 1);
+    let (_, in_containers) = occupied(&mut app);
+    let alive = app.world.query::<&Item>().iter(&app.world).count();
+    assert!(alive <= in_containers.len() + 1);
+}
+
+#[test]
+fn sorter_routes_by_rule() {
+    let mut app = app();
+    let sorter = IsoPos::origin();
+    let direction = IsoDirection::PosA;
+    let input = sorter.offset_direction(direction, -1);
+    let front = sorter.offset_direction(direction, 1);
+    let side = sorter
+        .surroundings()
+        .into_iter()
+        .find(|&pos| pos != input && pos != front)
+        .unwrap();
+    let side_direction = ALL_DIRECTIONS
+        .iter()
+        .copied()
+        .find(|&dir| side.offset_direction(dir, -1) == sorter)
+        .unwrap();
+    build(&mut app, BConveyor, input, direction);
+    build(
+        &mut app,
+        BSorter {
+            rule: ItemRule::Contains(Element::Impurity),
+        },
+        sorter,
+        direction,
+    );
+    // Matching items go into another building, the rest onto a belt.
+    build(&mut app, BDestroyer, front, direction);
+    build_line(&mut app, side, side_direction, 40);
+    run_ticks(&mut app, 1);
+
+    for tick in 0..100 {
+        let of = if tick % 16 < 8 {
+            ReferenceItem::Magnetite
+        } else {
+            ReferenceItem::IronLump
+        };
+        put_items(&mut app, &[input], of);
+        run_ticks(&mut app, 1);
+    }
+    let side_line: HashSet<IsoPos> = (0..40)
+        .map(|i| side.offset_direction(side_direction, i))
+        .collect();
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    assert!(stats.get(front).unwrap().items_in > 0);
+    assert!(
+        count_items(&mut app, ReferenceItem::IronLump, |pos| side_line
+            .contains(&pos))
+            > 0
+    );
+    assert_eq!(
+        count_items(&mut app, ReferenceItem::Magnetite, |pos| side_line
+            .contains(&pos)),
+        0
+    );
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::Command, prelude::*};
+use maplit::hashmap;
+use serde::{Deserialize, Serialize};
+
+use super::{spawn_belt_art, ConveyorLogic, ConveyorTier};
+use crate::{
+    buildable::{
+        storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
+        BuildingMaps, WhichMap,
+    },
+    item::{ItemContainer, ItemContainerAlignment, ReferenceItem},
+    prelude::*,
+};
+
+/// How far apart an entrance and its exit can be.
+pub const MAX_UNDERGROUND_LENGTH: u8 = 8;
+
+/// Sends items underground to a `BUndergroundExit` facing the same way
+/// exactly `length` cells ahead, passing under whatever is in between.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BUndergroundEntrance {
+    pub length: u8,
+}
+
+/// Brings items back up from a `BUndergroundEntrance` behind it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BUndergroundExit;
+
+#[derive(Component)]
+pub struct UndergroundEntrance {
+    pub(super) length: u8,
+    /// The items travelling underground, along with the tick each one reaches
+    /// the exit. The first one is the closest to the exit.
+    items: VecDeque<(Entity, u64)>,
+}
+
+#[derive(Component)]
+pub(super) struct UndergroundExit;
+
+/// Despawns the items travelling through the tunnel of an entrance. They are
+/// not in any container, so nothing else would ever get rid of them.
+struct DespawnTunnelItems(Entity);
+
+impl Command for DespawnTunnelItems {
+    fn write(self, world: &mut World) {
+        let items = world
+            .get_mut::<UndergroundEntrance>(self.0)
+            .map(|mut tunnel| std::mem::take(&mut tunnel.items));
+        for (item, _) in items.into_iter().flatten() {
+            world.despawn(item);
+        }
+    }
+}
+
+fn details(position: IsoPos, cost: ItemList) -> BuildingDetails {
+    BuildingDetails {
+        shape: vec![position],
+        maps: vec![
+            WhichMap::Buildings,
+            WhichMap::Conveyors,
+            WhichMap::ItemContainers,
+        ],
+        cost,
+    }
+}
+
+fn insert_components(ctx: &mut BuildingComponentsContext) {
+    ctx.commands
+        .insert(ConveyorLogic::new(ConveyorTier::Normal))
+        .insert(ItemContainer::new_empty(
+            ItemContainerAlignment::AxisAligned(ctx.direction.axis()),
+        ))
+        .insert(SetupNeeded);
+}
+
+impl Buildable for BUndergroundEntrance {
+    type ExtraData = ();
+    const ID: &'static str = "underground_entrance";
+    const NAME: &'static str = "Underground Entrance";
+
+    fn details(
+        &self,
+        position: IsoPos,
+        _direction: IsoDirection,
+        _maps: &BuildingMaps,
+    ) -> Option<BuildingDetails> {
+        if self.length == 0 || self.length > MAX_UNDERGROUND_LENGTH {
+            return None;
+        }
+        Some(details(
+            position,
+            ItemList::from_counts(hashmap![
+                ReferenceItem::IronLump.as_item() => 2 + self.length as u32,
+                ReferenceItem::PureAnimus.as_item() => 1,
+            ]),
+        ))
+    }
+
+    fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+        insert_components(ctx);
+        ctx.commands.insert(UndergroundEntrance {
+            length: self.length,
+            items: VecDeque::new(),
+        });
+    }
+
+    fn spawn_extras(
+        &self,
+        _ctx: &mut BuildingContext,
+        _maps: &mut BuildingMaps,
+    ) -> (Vec<Entity>, Self::ExtraData) {
+        (vec![], ())
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        let assets = ctx.common_assets;
+        spawn_belt_art(ctx, &assets.underground_conveyor_mat)
+    }
+
+    fn display_name(&self) -> String {
+        format!("{} ({} cells)", Self::NAME, self.length)
+    }
+
+    fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
+        if let Some(&entrance) = maps.conveyors.get(ctx.position) {
+            ctx.commands.add(DespawnTunnelItems(entrance));
+        }
+        // The exit is too far away to be relinked along with our neighbours.
+        let exit_pos = ctx
+            .position
+            .offset_direction(ctx.direction, self.length as i32);
+        if let Some(&exit) = maps.conveyors.get(exit_pos) {
+            ctx.commands.entity(exit).insert(SetupNeeded);
+        }
+    }
+}
+
+impl Buildable for BUndergroundExit {
+    type ExtraData = ();
+    const ID: &'static str = "underground_exit";
+    const NAME: &'static str = "Underground Exit";
+
+    fn details(
+        &self,
+        position: IsoPos,
+        _direction: IsoDirection,
+        _maps: &BuildingMaps,
+    ) -> Option<BuildingDetails> {
+        Some(details(
+            position,
+            ItemList::from_counts(hashmap![
+                ReferenceItem::IronLump.as_item() => 2,
+                ReferenceItem::PureAnimus.as_item() => 1,
+            ]),
+        ))
+    }
+
+    fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: ()) {
+        insert_components(ctx);
+        ctx.commands.insert(UndergroundExit);
+    }
+
+    fn spawn_extras(
+        &self,
+        _ctx: &mut BuildingContext,
+        _maps: &mut BuildingMaps,
+    ) -> (Vec<Entity>, Self::ExtraData) {
+        (vec![], ())
+    }
+
+    fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
+        let assets = ctx.common_assets;
+        spawn_belt_art(ctx, &assets.underground_conveyor_mat)
+    }
+
+    fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
+        // Any entrance that was sending items here has to stop.
+        for distance in 1..=MAX_UNDERGROUND_LENGTH as i32 {
+            let entrance_pos = ctx.position.offset_direction(ctx.direction, -distance);
+            if let Some(&entrance) = maps.conveyors.get(entrance_pos) {
+                ctx.commands.entity(entrance).insert(SetupNeeded);
+            }
+        }
+    }
+}
+
+impl UndergroundEntrance {
+    /// Takes an item underground if there is room for it. Items keep the same
+    /// spacing underground that they would have on a belt, so a tunnel
+    /// carries as many items as a belt of the same length.
+    pub(super) fn try_push(&mut self, item: Entity, duration: u8, tick_clock: &TickClock) -> bool {
+        let arrival = tick_clock.current_tick() + self.length as u64 * duration as u64;
+        let spaced_out = self.items.back().map_or(true, |&(_, last_arrival)| {
+            last_arrival + duration as u64 <= arrival
+        });
+        if self.items.len() >= self.length as usize || !spaced_out {
+            return false;
+        }
+        self.items.push_back((item, arrival));
+        true
+    }
+
+    /// Returns the item closest to the exit if it has arrived there.
+    pub(super) fn pop_arrived(&mut self, tick_clock: &TickClock) -> Option<Entity> {
+        let &(_, arrival) = self.items.front()?;
+        if arrival > tick_clock.current_tick() {
+            return None;
+        }
+        self.items.pop_front().map(|(item, _)| item)
+    }
+
+    /// The items travelling underground and the tick each one reaches the
+    /// exit, closest to the exit first.
+    pub fn items(&self) -> impl Iterator<Item = (Entity, u64)> + '_ {
+        self.items.iter().copied()
+    }
+
+    /// Puts the tunnel back into a previously saved state.
+    pub fn restore(&mut self, items: Vec<(Entity, u64)>) {
+        self.items = items.into();
+    }
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::{
+    stats::{Activity, Throughput},
     storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
     BuildingMaps, WhichMap,
 };
@@ -9,7 +11,7 @@ use crate::{
     prelude::*,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BDestroyer;
 
 #[derive(Component)]
@@ -17,6 +19,8 @@ pub struct DestroyerLogic;
 
 impl Buildable for BDestroyer {
     type ExtraData = ();
+    const ID: &'static str = "destroyer";
+    const NAME: &'static str = "Destroyer";
 
     fn details(
         &self,
@@ -34,6 +38,7 @@ impl Buildable for BDestroyer {
     fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, _data: Self::ExtraData) {
         ctx.commands
             .insert(DestroyerLogic)
+            .insert(Throughput::default())
             .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid));
     }
 
@@ -61,14 +66,22 @@ impl Buildable for BDestroyer {
 
 fn tick(
     mut commands: Commands,
-    mut destroyers: Query<(&mut ItemContainer,), With<DestroyerLogic>>,
+    tick_clock: Res<TickClock>,
+    mut destroyers: Query<(&mut ItemContainer, &mut Throughput), With<DestroyerLogic>>,
 ) {
-    for (mut container,) in destroyers.iter_mut() {
+    let tick = tick_clock.current_tick();
+    for (mut container, mut throughput) in destroyers.iter_mut() {
         if container.blocked() {
+            // An item is still on its way in.
+            throughput.record_activity(tick, Activity::Processing);
             continue;
         }
         if let Some(item) = container.try_take() {
             commands.entity(item).despawn();
+            throughput.record_in(tick, 1);
+            throughput.record_activity(tick, Activity::Processing);
+        } else {
+            throughput.record_activity(tick, Activity::Idle);
         }
     }
 }
@@ -1,13 +1,15 @@
 use bevy::prelude::*;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 
 use super::{
     machine::{self, Shape},
+    stats::{Activity, Throughput},
     storage::ItemList,
     Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails, BuildingMaps, WhichMap,
 };
 use crate::{
-    item::{ItemContainer, ItemContainerAlignment, ReferenceItem},
+    item::{ItemContainer, ItemContainerAlignment, OutputContainer, ReferenceItem},
     prelude::*,
     resource_nodes::ResourceNode,
 };
@@ -16,10 +18,9 @@ use crate::{
 pub struct Drill {
     node: ResourceNode,
     outputs: Vec<Entity>,
-    timer: u8,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BDrill;
 
 const SHAPE: Shape = Shape {
@@ -41,6 +42,8 @@ const SHAPE: Shape = Shape {
 
 impl Buildable for BDrill {
     type ExtraData = (Vec<Entity>, ResourceNode);
+    const ID: &'static str = "drill";
+    const NAME: &'static str = "Drill";
 
     fn details(
         &self,
@@ -63,11 +66,12 @@ impl Buildable for BDrill {
     }
 
     fn extra_root_components(&self, ctx: &mut BuildingComponentsContext, data: Self::ExtraData) {
-        ctx.commands.insert(Drill {
-            node: data.1,
-            outputs: data.0,
-            timer: 0,
-        });
+        ctx.commands
+            .insert(Drill {
+                node: data.1,
+                outputs: data.0,
+            })
+            .insert(Throughput::default());
     }
 
     fn spawn_extras(
@@ -75,19 +79,20 @@ impl Buildable for BDrill {
         ctx: &mut BuildingContext,
         maps: &mut BuildingMaps,
     ) -> (Vec<bevy::prelude::Entity>, Self::ExtraData) {
-        let mut inputs = Vec::new();
-        for pos in SHAPE.positions(ctx.position, ctx.direction).inputs {
+        let mut outputs = Vec::new();
+        for pos in SHAPE.positions(ctx.position, ctx.direction).outputs {
             let container = ctx
                 .commands
                 .spawn()
                 .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+                .insert(OutputContainer)
                 .insert(pos)
                 .id();
             maps.item_containers.set(pos, container);
-            inputs.push(container);
+            outputs.push(container);
         }
         let node = maps.resource_nodes.get(ctx.position).unwrap().clone();
-        (inputs.clone(), (inputs, node))
+        (outputs.clone(), (outputs, node))
     }
 
     fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<bevy::prelude::Entity> {
@@ -98,11 +103,14 @@ impl Buildable for BDrill {
 fn tick(
     mut commands: Commands,
     common_assets: Res<CommonAssets>,
-    mut drills: Query<(&mut Drill,)>,
+    tick_clock: Res<TickClock>,
+    mut drills: Query<(&mut Drill, &mut Throughput)>,
     mut containers: Query<(&IsoPos, &mut ItemContainer)>,
 ) {
-    for (mut drill,) in drills.iter_mut() {
+    let tick = tick_clock.current_tick();
+    for (mut drill, mut throughput) in drills.iter_mut() {
         let drill = &mut *drill;
+        let mut produced = 0;
         for &output in &drill.outputs {
             let (pos, mut container) = containers.get_mut(output).unwrap();
             if container.item().is_none() {
@@ -112,8 +120,16 @@ fn tick(
                     *pos,
                     drill.node.of.as_item(),
                 );
+                produced += 1;
             }
         }
+        throughput.record_out(tick, produced);
+        let activity = if produced > 0 {
+            Activity::Processing
+        } else {
+            Activity::Blocked
+        };
+        throughput.record_activity(tick, activity);
     }
 }
 
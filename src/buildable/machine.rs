@@ -7,6 +7,7 @@ use bevy::prelude::{App, Plugin};
 
 pub use self::{
     buildable::{spawn_placeholder_art, BMachine},
+    logic::MachineLogic,
     shape::*,
     typee::*,
 };
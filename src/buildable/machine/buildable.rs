@@ -1,16 +1,17 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::{logic::MachineLogic, shape::Shape, typee::MachineType};
 use crate::{
     buildable::{
-        storage::ItemList, Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails,
-        BuildingMaps, WhichMap,
+        stats::Throughput, storage::ItemList, Buildable, BuildingComponentsContext,
+        BuildingContext, BuildingDetails, BuildingMaps, WhichMap,
     },
-    item::{ItemContainer, ItemContainerAlignment},
+    item::{ItemContainer, ItemContainerAlignment, OutputContainer},
     prelude::*,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BMachine(pub MachineType);
 
 pub struct MachineIo {
@@ -20,6 +21,8 @@ pub struct MachineIo {
 
 impl Buildable for BMachine {
     type ExtraData = MachineIo;
+    const ID: &'static str = "machine";
+    const NAME: &'static str = "Machine";
 
     fn details(
         &self,
@@ -67,6 +70,7 @@ impl Buildable for BMachine {
                 .spawn()
                 .insert(pos)
                 .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+                .insert(OutputContainer)
                 .id();
             maps.item_containers.set_assuming_empty(pos, ent);
             io.outputs.push(ent);
@@ -81,7 +85,8 @@ impl Buildable for BMachine {
         MachineIo { inputs, outputs }: MachineIo,
     ) {
         ctx.commands
-            .insert(MachineLogic::new(inputs, outputs, self.0));
+            .insert(MachineLogic::new(inputs, outputs, self.0))
+            .insert(Throughput::default());
     }
 
     fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
@@ -92,6 +97,10 @@ impl Buildable for BMachine {
         }
     }
 
+    fn display_name(&self) -> String {
+        format!("{:?}", self.0)
+    }
+
     fn on_destroy(&self, ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
         let p = self.0.get_shape().positions(ctx.position, ctx.direction);
         for container in p.inputs.chain(p.outputs) {
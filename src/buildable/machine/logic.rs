@@ -2,6 +2,7 @@ use bevy::prelude::*;
 
 use super::typee::MachineType;
 use crate::{
+    buildable::stats::{Activity, Throughput},
     item::{Item, ItemContainer},
     prelude::*,
 };
@@ -22,6 +23,21 @@ impl MachineLogic {
         self.input_buffer.iter().all(Option::is_some)
     }
 
+    pub fn input_buffer(&self) -> &[Option<Item>] {
+        &self.input_buffer[..]
+    }
+
+    pub fn processing_time(&self) -> u8 {
+        self.processing_time
+    }
+
+    /// Puts the machine back into a previously saved state.
+    pub fn restore(&mut self, input_buffer: Vec<Option<Item>>, processing_time: u8) {
+        assert_eq!(input_buffer.len(), self.inputs.len());
+        self.input_buffer = input_buffer;
+        self.processing_time = processing_time;
+    }
+
     pub(super) fn new(inputs: Vec<Entity>, outputs: Vec<Entity>, typ: MachineType) -> Self {
         Self {
             input_buffer: vec![None; inputs.len()],
@@ -36,11 +52,13 @@ impl MachineLogic {
 fn tick(
     mut commands: Commands,
     common_assets: Res<CommonAssets>,
-    mut machines: Query<(&mut MachineLogic,)>,
+    tick_clock: Res<TickClock>,
+    mut machines: Query<(&mut MachineLogic, &mut Throughput)>,
     mut containers: Query<(&mut ItemContainer, &IsoPos)>,
     items: Query<&Item>,
 ) {
-    for (mut machine,) in machines.iter_mut() {
+    let tick = tick_clock.current_tick();
+    for (mut machine, mut throughput) in machines.iter_mut() {
         let done = machine.processing_time == machine.typ.processing_time();
         let mut can_output = done;
         for &output in &machine.outputs {
@@ -51,6 +69,15 @@ fn tick(
             }
         }
 
+        let activity = if !machine.processing() {
+            Activity::Idle
+        } else if done && !can_output {
+            Activity::Blocked
+        } else {
+            Activity::Processing
+        };
+        throughput.record_activity(tick, activity);
+
         if machine.processing() {
             if done && can_output {
                 let mut inputs = Vec::new();
@@ -59,6 +86,7 @@ fn tick(
                 }
                 let results = machine.typ.process(inputs);
                 assert_eq!(results.len(), machine.outputs.len());
+                throughput.record_out(tick, results.len() as u32);
                 for (result, &output) in results.into_iter().zip(machine.outputs.iter()) {
                     let (mut output, pos) = containers.get_mut(output).unwrap();
                     output.create_and_put_item(&mut commands, &common_assets, *pos, result);
@@ -80,6 +108,7 @@ fn tick(
                     commands.entity(item).despawn();
                     let item = items.get(item).unwrap().clone();
                     *buffer = Some(item);
+                    throughput.record_in(tick, 1);
                 }
             }
         }
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use itertools::Itertools;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     buildable::{machine::shape::Shape, storage::ItemList},
@@ -8,7 +9,7 @@ use crate::{
     prelude::*,
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MachineType {
     Purifier,
     Joiner,
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Buildable, DynBuildable};
+
+/// A buildable in a form that can be written to disk. `data` is the RON
+/// representation of the buildable whose kind is `id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedBuildable {
+    pub id: String,
+    pub data: String,
+}
+
+pub struct BuildableKind {
+    pub id: &'static str,
+    pub name: &'static str,
+    deserialize: fn(&str) -> ron::Result<Box<dyn DynBuildable>>,
+}
+
+impl BuildableKind {
+    fn of<B: Buildable>() -> Self {
+        Self {
+            id: B::ID,
+            name: B::NAME,
+            deserialize: |data| Ok(Box::new(ron::de::from_str::<B>(data)?)),
+        }
+    }
+}
+
+/// Every kind of buildable, indexed by ID.
+#[derive(Default)]
+pub struct BuildableRegistry {
+    kinds: HashMap<&'static str, BuildableKind>,
+}
+
+impl BuildableRegistry {
+    pub fn register<B: Buildable>(&mut self) {
+        let previous = self.kinds.insert(B::ID, BuildableKind::of::<B>());
+        assert!(previous.is_none(), "{} was registered twice", B::ID);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&BuildableKind> {
+        self.kinds.get(id)
+    }
+
+    pub fn kinds(&self) -> impl Iterator<Item = &BuildableKind> {
+        self.kinds.values()
+    }
+
+    /// Returns None if `saved` refers to a kind that was never registered.
+    pub fn load(&self, saved: &SavedBuildable) -> Option<ron::Result<Box<dyn DynBuildable>>> {
+        let kind = self.get(&saved.id)?;
+        Some((kind.deserialize)(&saved.data))
+    }
+}
@@ -1,6 +1,10 @@
 use bevy::prelude::*;
 
-use super::{BuildingContext, BuildingDetails, BuildingMaps, Built, DynBuildable};
+use super::{
+    conveyor::relink_conveyors_near, BuildingContext, BuildingDetails, BuildingMaps, Built,
+    DynBuildable,
+};
+use crate::prelude::*;
 
 pub fn spawn_buildable(
     buildable: Box<dyn DynBuildable>,
@@ -13,7 +17,8 @@ pub fn spawn_buildable(
         direction: ctx.direction,
     };
     let root = buildable.spawn_self(built, ctx, maps);
-    set_positions_on_maps(&buildable, maps, ctx, root);
+    let shape = set_positions_on_maps(&buildable, maps, ctx, root);
+    relink_conveyors_near(&shape, ctx.commands, maps);
     root
 }
 
@@ -22,7 +27,7 @@ fn set_positions_on_maps(
     maps: &mut BuildingMaps,
     ctx: &mut BuildingContext,
     root: Entity,
-) {
+) -> Vec<IsoPos> {
     let BuildingDetails {
         maps: requested_maps,
         shape,
@@ -31,11 +36,11 @@ fn set_positions_on_maps(
         .details(ctx.position, ctx.direction, maps)
         .unwrap();
     for map in requested_maps {
-        let map = map.get_from_maps_mut(maps);
         for &pos in &shape {
-            map.set(pos, root);
+            map.set(maps, pos, root);
         }
     }
+    shape
 }
 
 pub fn destroy_buildable(
@@ -43,22 +48,27 @@ pub fn destroy_buildable(
     ctx: &mut BuildingContext,
     maps: &mut BuildingMaps,
 ) {
+    ctx.position = buildable.1.position;
+    ctx.direction = buildable.1.direction;
+    // Called first, so that any commands it adds for the building itself run
+    // while the building still exists.
+    buildable.1.buildable.on_destroy(ctx, maps);
     // The spawner parents everything to the root entity, so this will take care
     // of all art and other related entities as well as the buildable object
     // itself.
-    ctx.position = buildable.1.position;
-    ctx.direction = buildable.1.direction;
-    ctx.commands.entity(buildable.0).despawn_recursive();
+    let root = buildable.0;
+    ctx.commands.entity(root).despawn_recursive();
     let buildable = &buildable.1.buildable;
-    buildable.on_destroy(ctx, maps);
-    clear_positions_on_maps(buildable, maps, ctx);
+    let shape = clear_positions_on_maps(buildable, maps, ctx, root);
+    relink_conveyors_near(&shape, ctx.commands, maps);
 }
 
 fn clear_positions_on_maps(
     buildable: &Box<dyn DynBuildable>,
     maps: &mut BuildingMaps,
     ctx: &mut BuildingContext,
-) {
+    root: Entity,
+) -> Vec<IsoPos> {
     let BuildingDetails {
         maps: requested_maps,
         shape,
@@ -67,9 +77,9 @@ fn clear_positions_on_maps(
         .details(ctx.position, ctx.direction, maps)
         .unwrap();
     for map in requested_maps {
-        let map = map.get_from_maps_mut(maps);
         for &pos in &shape {
-            map.clear(pos);
+            map.clear(maps, pos, root);
         }
     }
+    shape
 }
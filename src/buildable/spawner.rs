@@ -1,13 +1,18 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use super::{storage::ItemList, Buildable, BuildingDetails, BuildingMaps};
+use super::{
+    stats::{Activity, Throughput},
+    storage::ItemList,
+    Buildable, BuildingDetails, BuildingMaps,
+};
 use crate::{
     buildable::WhichMap,
-    item::{spawn_item, ItemContainer, ItemContainerAlignment, ReferenceItem},
+    item::{spawn_item, ItemContainer, ItemContainerAlignment, OutputContainer, ReferenceItem},
     prelude::*,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BSpawner {
     pub interval: u8,
     pub item: ReferenceItem,
@@ -21,8 +26,21 @@ pub struct SpawnerLogic {
     timer: u8,
 }
 
+impl SpawnerLogic {
+    pub fn timer(&self) -> u8 {
+        self.timer
+    }
+
+    /// Puts the spawner back into a previously saved state.
+    pub fn restore(&mut self, timer: u8) {
+        self.timer = timer;
+    }
+}
+
 impl Buildable for BSpawner {
     type ExtraData = ();
+    const ID: &'static str = "spawner";
+    const NAME: &'static str = "Spawner";
 
     fn details(
         &self,
@@ -48,7 +66,9 @@ impl Buildable for BSpawner {
                 interval: self.interval,
                 timer: 0,
             })
-            .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid));
+            .insert(Throughput::default())
+            .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+            .insert(OutputContainer);
     }
 
     fn spawn_extras(
@@ -76,12 +96,20 @@ impl Buildable for BSpawner {
 fn tick(
     mut commands: Commands,
     common_assets: Res<CommonAssets>,
-    mut spawners: Query<(&mut SpawnerLogic, &mut ItemContainer, &IsoPos)>,
+    tick_clock: Res<TickClock>,
+    mut spawners: Query<(&mut SpawnerLogic, &mut Throughput, &mut ItemContainer, &IsoPos)>,
 ) {
-    for (mut spawner, mut container, pos) in spawners.iter_mut() {
+    let tick = tick_clock.current_tick();
+    for (mut spawner, mut throughput, mut container, pos) in spawners.iter_mut() {
         if spawner.timer > 0 {
             spawner.timer -= 1;
         }
+        let activity = if spawner.timer == 0 && container.item().is_some() {
+            Activity::Blocked
+        } else {
+            Activity::Processing
+        };
+        throughput.record_activity(tick, activity);
         if spawner.timer == 0 && container.item().is_none() {
             spawner.timer = spawner.interval;
             let item = spawn_item(
@@ -93,6 +121,7 @@ fn tick(
             );
             container.put_item(item);
             container.set_blocked(false);
+            throughput.record_out(tick, 1);
         }
     }
 }
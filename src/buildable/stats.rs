@@ -0,0 +1,141 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+/// What a building spent a tick doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activity {
+    /// Waiting for something to work on.
+    Idle,
+    /// Finished working but has nowhere to put the result.
+    Blocked,
+    Processing,
+}
+
+/// Counts how many items a building has taken in and put out, and how it has
+/// spent its time. Buildings update this from their own tick systems.
+#[derive(Clone, Component, Debug, Default)]
+pub struct Throughput {
+    items_in: u64,
+    items_out: u64,
+    ticks_idle: u64,
+    ticks_blocked: u64,
+    ticks_processing: u64,
+    /// The ticks on which each item came in during the last minute.
+    recent_in: VecDeque<u64>,
+    /// The ticks on which each item went out during the last minute.
+    recent_out: VecDeque<u64>,
+}
+
+/// A snapshot of a Throughput, with rates measured over the last minute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThroughputSummary {
+    pub items_in: u64,
+    pub items_out: u64,
+    pub ticks_idle: u64,
+    pub ticks_blocked: u64,
+    pub ticks_processing: u64,
+    pub items_in_per_minute: u32,
+    pub items_out_per_minute: u32,
+}
+
+impl Throughput {
+    pub fn record_in(&mut self, tick: u64, count: u32) {
+        self.items_in += count as u64;
+        self.recent_in.extend((0..count).map(|_| tick));
+    }
+
+    pub fn record_out(&mut self, tick: u64, count: u32) {
+        self.items_out += count as u64;
+        self.recent_out.extend((0..count).map(|_| tick));
+    }
+
+    /// Should be called exactly once per tick.
+    pub fn record_activity(&mut self, tick: u64, activity: Activity) {
+        match activity {
+            Activity::Idle => self.ticks_idle += 1,
+            Activity::Blocked => self.ticks_blocked += 1,
+            Activity::Processing => self.ticks_processing += 1,
+        }
+        for recent in [&mut self.recent_in, &mut self.recent_out].iter_mut() {
+            while recent.front().map_or(false, |&t| !within_last_minute(t, tick)) {
+                recent.pop_front();
+            }
+        }
+    }
+
+    pub fn summary(&self, current_tick: u64) -> ThroughputSummary {
+        let per_minute = |recent: &VecDeque<u64>| {
+            recent
+                .iter()
+                .filter(|&&t| within_last_minute(t, current_tick))
+                .count() as u32
+        };
+        ThroughputSummary {
+            items_in: self.items_in,
+            items_out: self.items_out,
+            ticks_idle: self.ticks_idle,
+            ticks_blocked: self.ticks_blocked,
+            ticks_processing: self.ticks_processing,
+            items_in_per_minute: per_minute(&self.recent_in),
+            items_out_per_minute: per_minute(&self.recent_out),
+        }
+    }
+}
+
+fn within_last_minute(tick: u64, current_tick: u64) -> bool {
+    tick + TickClock::TICKS_PER_MINUTE > current_tick
+}
+
+impl ThroughputSummary {
+    pub fn summary(&self) -> String {
+        format!(
+            "In: {} ({}/min)\nOut: {} ({}/min)\nIdle: {}t Blocked: {}t Busy: {}t",
+            self.items_in,
+            self.items_in_per_minute,
+            self.items_out,
+            self.items_out_per_minute,
+            self.ticks_idle,
+            self.ticks_blocked,
+            self.ticks_processing
+        )
+    }
+}
+
+/// The throughput of every building that tracks it, by the position of the
+/// building. Refreshed every frame after ticking.
+#[derive(Default)]
+pub struct ThroughputStats(HashMap<IsoPos, ThroughputSummary>);
+
+impl ThroughputStats {
+    pub fn get(&self, building_pos: IsoPos) -> Option<&ThroughputSummary> {
+        self.0.get(&building_pos)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&IsoPos, &ThroughputSummary)> {
+        self.0.iter()
+    }
+}
+
+fn collect(
+    tick_clock: Res<TickClock>,
+    mut stats: ResMut<ThroughputStats>,
+    buildings: Query<(&IsoPos, &Throughput)>,
+) {
+    let current_tick = tick_clock.current_tick();
+    stats.0.clear();
+    for (&pos, throughput) in buildings.iter() {
+        stats.0.insert(pos, throughput.summary(current_tick));
+    }
+}
+
+pub struct Plug;
+
+impl Plugin for Plug {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ThroughputStats::default())
+            .add_system_to_stage(fstage::ANIMATION, collect.system());
+    }
+}
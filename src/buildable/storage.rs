@@ -3,17 +3,18 @@ use std::collections::HashMap;
 use bevy::prelude::{App, Commands, Component, DespawnRecursiveExt, Entity, Plugin, Query};
 use itertools::Itertools;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 
 use super::{
     machine::{self, Shape},
     Buildable, BuildingComponentsContext, BuildingContext, BuildingDetails, BuildingMaps, WhichMap,
 };
 use crate::{
-    item::{Item, ItemContainer, ItemContainerAlignment, ReferenceItem},
+    item::{Item, ItemContainer, ItemContainerAlignment, OutputContainer, ReferenceItem},
     prelude::{fstage, IsoDirection, IsoPos},
 };
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ItemList(HashMap<Item, u32>);
 
 impl ItemList {
@@ -37,6 +38,13 @@ impl ItemList {
         }
     }
 
+    /// Adds every item in `other` to this list.
+    pub fn add_list(&mut self, other: &ItemList) {
+        for (item, &count) in &other.0 {
+            self.add_bulk(item.clone(), count);
+        }
+    }
+
     pub fn count(&self, of: &Item) -> u32 {
         self.0.get(of).copied().unwrap_or(0)
     }
@@ -85,6 +93,11 @@ impl ItemList {
     }
 }
 
+/// Marks a container which claws can pull stored items out of. The warehouse
+/// it belongs to is its parent.
+#[derive(Component)]
+pub struct StorageOutput;
+
 #[derive(Component)]
 pub struct Storage {
     inputs: Vec<Entity>,
@@ -112,6 +125,16 @@ impl Storage {
         self.items.count(of)
     }
 
+    pub fn items(&self) -> &ItemList {
+        &self.items
+    }
+
+    /// Replaces everything in storage, ignoring the volume limit.
+    pub fn set_items(&mut self, items: ItemList) {
+        self.item_volume = items.total_volume();
+        self.items = items;
+    }
+
     /// Returns the actual number of items removed, which may be less than the
     /// requested count if the requested count is greater than what's available.
     pub fn remove_bulk(&mut self, item: &Item, count: u32) -> u32 {
@@ -120,6 +143,20 @@ impl Storage {
         count
     }
 
+    /// Removes one of the first item, in sorted order, that `wanted` accepts.
+    pub fn remove_first(&mut self, wanted: impl Fn(&Item) -> bool) -> Option<Item> {
+        let item = self
+            .items
+            .0
+            .iter()
+            .filter(|&(item, &count)| count > 0 && wanted(item))
+            .map(|(item, _)| item)
+            .min()?
+            .clone();
+        self.remove_bulk(&item, 1);
+        Some(item)
+    }
+
     /// Does not modify `self`.
     pub fn subtract_available_inventory_from(&self, list: &mut ItemList) {
         for (item, count) in &mut list.0 {
@@ -147,7 +184,7 @@ impl Storage {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BSmallWarehouse(pub ItemList);
 
 const SHAPE: Shape = Shape {
@@ -157,18 +194,18 @@ const SHAPE: Shape = Shape {
         (1, 3),
         (1, -3),
         (0, 3),
-        (0, 2),
         (0, 1),
         (0, -1),
-        (0, -2),
         (0, -3),
     ],
     inputs: &[(1, 0), (1, 2), (1, -2)],
-    outputs: &[],
+    outputs: &[(0, 2), (0, -2)],
 };
 
 impl Buildable for BSmallWarehouse {
     type ExtraData = Vec<Entity>;
+    const ID: &'static str = "small_warehouse";
+    const NAME: &'static str = "Small Warehouse";
 
     fn details(
         &self,
@@ -200,8 +237,9 @@ impl Buildable for BSmallWarehouse {
         ctx: &mut BuildingContext,
         maps: &mut BuildingMaps,
     ) -> (Vec<bevy::prelude::Entity>, Self::ExtraData) {
+        let positions = SHAPE.positions(ctx.position, ctx.direction);
         let mut inputs = Vec::new();
-        for pos in SHAPE.positions(ctx.position, ctx.direction).inputs {
+        for pos in positions.inputs {
             let container = ctx
                 .commands
                 .spawn()
@@ -211,7 +249,22 @@ impl Buildable for BSmallWarehouse {
             maps.item_containers.set(pos, container);
             inputs.push(container);
         }
-        (inputs.clone(), inputs)
+        // Outputs stay empty. Claws taking from one get an item straight out
+        // of storage instead, and nothing else is allowed to put items in.
+        let mut children = inputs.clone();
+        for pos in positions.outputs {
+            let container = ctx
+                .commands
+                .spawn()
+                .insert(ItemContainer::new_empty(ItemContainerAlignment::Centroid))
+                .insert(StorageOutput)
+                .insert(OutputContainer)
+                .insert(pos)
+                .id();
+            maps.item_containers.set(pos, container);
+            children.push(container);
+        }
+        (children, inputs)
     }
 
     fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<bevy::prelude::Entity> {
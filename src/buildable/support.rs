@@ -4,8 +4,9 @@ use bevy::{
 };
 
 use crate::{
-    iso::{ItemContainerMap, SpatialMap, ClawMap, },
-    prelude::*, resource_nodes::ResourceNodeMap,
+    iso::{ClawMap, ItemContainerMap},
+    prelude::*,
+    resource_nodes::ResourceNodeMap,
 };
 
 pub struct BuildingContext<'a, 'c1, 'c2> {
@@ -42,12 +43,24 @@ pub enum WhichMap {
 }
 
 impl WhichMap {
-    pub fn get_from_maps_mut<'b>(self, maps: &'b mut BuildingMaps) -> &'b mut SpatialMap<Entity> {
+    pub fn set(self, maps: &mut BuildingMaps, pos: IsoPos, entity: Entity) {
         match self {
+            WhichMap::Claws => maps.claws.add(pos, entity),
+            WhichMap::Buildings => maps.buildings.set(pos, entity),
+            WhichMap::Conveyors => maps.conveyors.set(pos, entity),
+            WhichMap::ItemContainers => maps.item_containers.set(pos, entity),
+        }
+    }
+
+    /// Undoes `set`. Only claws can share a position, so every other map is
+    /// cleared no matter what is in it.
+    pub fn clear(self, maps: &mut BuildingMaps, pos: IsoPos, entity: Entity) {
+        let map = match self {
+            WhichMap::Claws => return maps.claws.remove(pos, entity),
             WhichMap::Buildings => &mut **maps.buildings,
-            WhichMap::Claws => &mut **maps.claws,
             WhichMap::Conveyors => &mut **maps.conveyors,
             WhichMap::ItemContainers => &mut **maps.item_containers,
-        }
+        };
+        map.clear(pos);
     }
 }
@@ -1,3 +1,5 @@
+mod tests;
+
 use bevy::{
     ecs::{schedule::ShouldRun, system::EntityCommands},
     prelude::*,
@@ -30,30 +32,138 @@ pub mod fstage {
 #[derive(Component)]
 pub struct SetupNeeded;
 
-#[derive(Default)]
 pub struct TickClock {
     tick_progress: f32,
-    tick_this_frame: bool,
+    /// How many more times the tick stage needs to run this frame.
+    ticks_owed: u32,
+    /// How many ticks have happened since the game started, including the
+    /// ones run this frame.
+    current_tick: u64,
+    /// When true, every frame advances the clock by exactly one tick instead
+    /// of by however much real time has passed.
+    fixed_step: bool,
+    paused: bool,
+    /// Set when a single tick should be run while paused.
+    step_requested: bool,
+    /// How many times faster than normal the clock runs.
+    speed: u8,
+    /// Disabling interpolation makes it easier to verify the soundness of
+    /// ticking mechanisms.
+    interpolation: bool,
+    /// When set, no ticks after this one will run until it is changed.
+    tick_limit: Option<u64>,
+}
+
+impl Default for TickClock {
+    fn default() -> Self {
+        Self {
+            tick_progress: 0.0,
+            ticks_owed: 0,
+            current_tick: 0,
+            fixed_step: false,
+            paused: false,
+            step_requested: false,
+            speed: 1,
+            interpolation: true,
+            tick_limit: None,
+        }
+    }
 }
 
 impl TickClock {
-    #[cfg(not(feature = "quarter-speed"))]
+    /// If a frame takes so long that more ticks than this are owed, the extra
+    /// ticks are dropped instead of making the next frame take even longer.
+    const MAX_TICKS_PER_FRAME: u32 = 8;
     const TICK_SPEED: f32 = 60.0 / 360.0;
-    #[cfg(feature = "quarter-speed")]
-    const TICK_SPEED: f32 = 60.0 / 360.0 * 4.0;
+    /// How many ticks happen in one minute of game time at normal speed.
+    pub const TICKS_PER_MINUTE: u64 = (60.0 / Self::TICK_SPEED) as u64;
+
+    /// A clock which ticks exactly once per frame, so that running the same
+    /// number of frames always produces the same factory.
+    pub fn new_fixed_step() -> Self {
+        Self {
+            fixed_step: true,
+            ..Default::default()
+        }
+    }
 
     pub fn get_tick_progress(&self) -> f32 {
-        self.tick_progress / Self::TICK_SPEED
+        if self.interpolation {
+            self.tick_progress / Self::TICK_SPEED
+        } else {
+            0.0
+        }
+    }
+
+    /// The number of the most recent tick. While the tick stage is running,
+    /// this is the number of the tick currently being run.
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    /// Used when loading a save, so that everything timed by tick number
+    /// carries on from where it was.
+    pub fn set_current_tick(&mut self, current_tick: u64) {
+        self.current_tick = current_tick;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
-    pub fn is_tick_this_frame(&self) -> bool {
-        self.tick_this_frame
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Runs exactly one tick next frame. Does nothing unless paused.
+    pub fn request_step(&mut self) {
+        self.step_requested = self.paused;
+    }
+
+    pub fn speed(&self) -> u8 {
+        self.speed
+    }
+
+    /// A speed of zero is treated as normal speed. Use `toggle_paused` to
+    /// stop the clock instead.
+    pub fn set_speed(&mut self, speed: u8) {
+        self.speed = speed.max(1);
+    }
+
+    pub fn toggle_interpolation(&mut self) {
+        self.interpolation = !self.interpolation;
+    }
+
+    /// Keeps the clock from running past `limit`, so that something which
+    /// has to happen right after a particular tick cannot be skipped over by
+    /// a frame that runs several ticks at once.
+    pub fn set_tick_limit(&mut self, limit: Option<u64>) {
+        self.tick_limit = limit;
     }
 
     fn advance(&mut self, dt: f32) {
-        self.tick_progress += dt;
-        self.tick_this_frame = self.tick_progress >= Self::TICK_SPEED;
-        self.tick_progress %= Self::TICK_SPEED;
+        if self.paused {
+            self.ticks_owed = if std::mem::take(&mut self.step_requested) {
+                1
+            } else {
+                0
+            };
+            return;
+        }
+        self.tick_progress += if self.fixed_step {
+            Self::TICK_SPEED
+        } else {
+            dt * self.speed as f32
+        };
+        let owed = (self.tick_progress / Self::TICK_SPEED).floor();
+        self.tick_progress -= owed * Self::TICK_SPEED;
+        self.ticks_owed = (owed as u32).min(Self::MAX_TICKS_PER_FRAME);
+    }
+
+    /// Called right before the tick stage runs.
+    fn begin_tick(&mut self) {
+        self.ticks_owed -= 1;
+        self.current_tick += 1;
     }
 }
 
@@ -61,9 +171,14 @@ fn update_clock(time: Res<Time>, mut tick_clock: ResMut<TickClock>) {
     tick_clock.advance(time.delta_seconds());
 }
 
-fn only_on_tick(tick_clock: Res<TickClock>) -> ShouldRun {
-    if tick_clock.is_tick_this_frame() {
-        ShouldRun::Yes
+/// Runs the tick stage once for every tick owed this frame.
+fn run_owed_ticks(mut tick_clock: ResMut<TickClock>) -> ShouldRun {
+    let limited = tick_clock
+        .tick_limit
+        .map_or(false, |limit| tick_clock.current_tick >= limit);
+    if tick_clock.ticks_owed > 0 && !limited {
+        tick_clock.begin_tick();
+        ShouldRun::YesAndCheckAgain
     } else {
         ShouldRun::No
     }
@@ -106,7 +221,7 @@ impl Plugin for Plug {
             .add_stage_after(
                 fstage::SETUP,
                 fstage::TICK,
-                SystemStage::parallel().with_run_criteria(only_on_tick.system()),
+                SystemStage::parallel().with_run_criteria(run_owed_ticks.system()),
             )
             .add_stage_after(fstage::TICK, fstage::ANIMATION, SystemStage::parallel())
             .insert_resource(TickClock::default())
@@ -0,0 +1,65 @@
+#![cfg(test)]
+
+use super::*;
+
+/// How many times the tick stage has run.
+#[derive(Default)]
+struct TicksRun(u32);
+
+fn count_tick(mut ticks_run: ResMut<TicksRun>) {
+    ticks_run.0 += 1;
+}
+
+/// An app with nothing but the factory stages and a system counting how often
+/// the tick stage runs. Time never passes on its own, so every frame only runs
+/// the ticks owed from whatever progress the clock was given beforehand.
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugin(Plug)
+        .insert_resource(Time::default())
+        .insert_resource(TicksRun::default())
+        .add_system_to_stage(fstage::TICK, count_tick.system());
+    app
+}
+
+fn owe_ticks(app: &mut App, ticks: f32) {
+    let mut tick_clock = app.world.get_resource_mut::<TickClock>().unwrap();
+    tick_clock.tick_progress = ticks * TickClock::TICK_SPEED;
+}
+
+fn ticks_run(app: &App) -> u32 {
+    app.world.get_resource::<TicksRun>().unwrap().0
+}
+
+#[test]
+fn runs_every_owed_tick_in_one_frame() {
+    let mut app = app();
+    owe_ticks(&mut app, 3.5);
+    app.update();
+    assert_eq!(ticks_run(&app), 3);
+    let tick_clock = app.world.get_resource::<TickClock>().unwrap();
+    assert_eq!(tick_clock.current_tick(), 3);
+    // The leftover half tick is kept for later frames.
+    assert!((tick_clock.get_tick_progress() - 0.5).abs() < 1e-3);
+}
+
+#[test]
+fn drops_ticks_beyond_the_frame_limit() {
+    let mut app = app();
+    owe_ticks(&mut app, 20.0);
+    app.update();
+    assert_eq!(ticks_run(&app), TickClock::MAX_TICKS_PER_FRAME);
+    // The extra ticks are gone instead of being run on the next frame.
+    app.update();
+    assert_eq!(ticks_run(&app), TickClock::MAX_TICKS_PER_FRAME);
+}
+
+#[test]
+fn speed_multiplies_ticks_owed() {
+    let mut tick_clock = TickClock::default();
+    tick_clock.set_speed(4);
+    tick_clock.advance(TickClock::TICK_SPEED * 1.6);
+    assert_eq!(tick_clock.ticks_owed, 6);
+    tick_clock.set_speed(0);
+    assert_eq!(tick_clock.speed(), 1);
+}
@@ -0,0 +1,116 @@
+//! Runs the factory without a window, a GPU or any loaded assets. Every frame
+//! advances the simulation by exactly one tick, so the same sequence of builds
+//! always produces the same factory.
+
+mod tests;
+
+use bevy::prelude::*;
+
+use crate::{
+    buildable::{
+        self, destroy_buildable, spawn_buildable, BuildingContext, BuildingMaps, Built,
+        DynBuildable,
+    },
+    common, iso, item,
+    prelude::*,
+    resource_nodes,
+};
+
+/// Buildables which will be placed at the start of the next frame.
+#[derive(Default)]
+pub struct PendingBuilds(Vec<(Box<dyn DynBuildable>, IsoPos, IsoDirection)>);
+
+/// Positions of buildings which will be destroyed at the start of the next
+/// frame.
+#[derive(Default)]
+pub struct PendingDestroys(Vec<IsoPos>);
+
+pub fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(iso::Plug)
+        .add_plugin(common::Plug)
+        .add_plugin(buildable::Plug)
+        .add_plugin(item::Plug)
+        .add_plugin(resource_nodes::Plug)
+        .insert_resource(CommonAssets::default())
+        .insert_resource(TickClock::new_fixed_step())
+        .insert_resource(PendingBuilds::default())
+        .insert_resource(PendingDestroys::default())
+        .add_system_to_stage(fstage::UI_POST, spawn_pending.system())
+        .add_system_to_stage(fstage::UI_POST, destroy_pending.system());
+    app
+}
+
+/// Queues a buildable to be placed during the next call to `run_ticks`.
+pub fn build(
+    app: &mut App,
+    buildable: impl DynBuildable,
+    position: IsoPos,
+    direction: IsoDirection,
+) {
+    app.world
+        .get_resource_mut::<PendingBuilds>()
+        .unwrap()
+        .0
+        .push((Box::new(buildable), position, direction));
+}
+
+/// Queues the building at `position` to be destroyed during the next call to
+/// `run_ticks`.
+pub fn destroy(app: &mut App, position: IsoPos) {
+    app.world
+        .get_resource_mut::<PendingDestroys>()
+        .unwrap()
+        .0
+        .push(position);
+}
+
+pub fn run_ticks(app: &mut App, ticks: u32) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+fn spawn_pending(
+    mut commands: Commands,
+    common_assets: Res<CommonAssets>,
+    mut pending: ResMut<PendingBuilds>,
+    mut maps: BuildingMaps,
+) {
+    for (buildable, position, direction) in pending.0.drain(..) {
+        let mut ctx = BuildingContext {
+            commands: &mut commands,
+            position,
+            direction,
+            common_assets: &common_assets,
+        };
+        spawn_buildable(buildable, &mut ctx, &mut maps);
+    }
+}
+
+fn destroy_pending(
+    mut commands: Commands,
+    common_assets: Res<CommonAssets>,
+    mut pending: ResMut<PendingDestroys>,
+    mut maps: BuildingMaps,
+    built: Query<&Built>,
+) {
+    for position in pending.0.drain(..) {
+        let building = match maps.buildings.get(position) {
+            Some(&building) => building,
+            None => continue,
+        };
+        let mut ctx = BuildingContext {
+            commands: &mut commands,
+            position,
+            direction: IsoDirection::default(),
+            common_assets: &common_assets,
+        };
+        destroy_buildable(
+            (building, built.get(building).unwrap()),
+            &mut ctx,
+            &mut maps,
+        );
+    }
+}
@@ -0,0 +1,441 @@
+#![cfg(test)]
+
+use std::collections::HashSet;
+
+use bevy::ecs::system::SystemState;
+
+use super::*;
+use crate::{
+    buildable::{
+        claw::{BClaw, BWaypointClaw, ClawLogic, ClawPlacementError, ClawStop, StopKind},
+        destroyer::BDestroyer,
+        drill::BDrill,
+        spawner::BSpawner,
+        stats::ThroughputStats,
+        storage::{BSmallWarehouse, ItemList, Storage},
+    },
+    item::{Element, ItemFilter, ItemRule, ReferenceItem},
+};
+
+/// Builds a spawner of magnetite which makes an item every `interval` ticks.
+fn build_spawner(app: &mut App, position: IsoPos, interval: u8) {
+    build(
+        app,
+        BSpawner {
+            item: ReferenceItem::Magnetite,
+            interval,
+        },
+        position,
+        IsoDirection::default(),
+    );
+}
+
+/// Builds a spawner of magnetite at `source`, a destroyer at `sink` and a
+/// claw with `filter` and `priority` carrying items between them.
+fn spawner_to_destroyer(
+    app: &mut App,
+    source: IsoPos,
+    sink: IsoPos,
+    filter: ItemFilter,
+    priority: u8,
+) {
+    build_spawner(app, source, 8);
+    build(app, BDestroyer, sink, IsoDirection::default());
+    build(
+        app,
+        BClaw {
+            take_from: source,
+            filter,
+            priority,
+        },
+        sink,
+        IsoDirection::default(),
+    );
+}
+
+#[test]
+fn spawner_claw_destroyer() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    spawner_to_destroyer(&mut app, source, sink, ItemFilter::Any, 0);
+    run_ticks(&mut app, 200);
+    // Everything the spawner makes gets destroyed, so at most the item in the
+    // spawner and the item in the claw can exist at once.
+    let items = app.world.query::<&Item>().iter(&app.world).count();
+    assert!(items <= 2);
+}
+
+#[test]
+fn throughput_stats() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    spawner_to_destroyer(&mut app, source, sink, ItemFilter::Any, 0);
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let spawner = stats.get(source).unwrap();
+    let destroyer = stats.get(sink).unwrap();
+    assert!(destroyer.items_in > 0);
+    assert!(spawner.items_out >= destroyer.items_in);
+    assert!(spawner.items_out - destroyer.items_in <= 2);
+    // Less than a minute has passed, so everything counts towards the rate.
+    assert_eq!(spawner.items_out_per_minute as u64, spawner.items_out);
+    assert!(destroyer.ticks_idle > 0);
+}
+
+#[test]
+fn drill_reports_throughput() {
+    let mut app = app();
+    let node = IsoPos::origin();
+    let mut state: SystemState<(Commands, Res<CommonAssets>, BuildingMaps)> =
+        SystemState::new(&mut app.world);
+    let (mut commands, common_assets, mut maps) = state.get_mut(&mut app.world);
+    resource_nodes::spawn_resource_node(
+        &mut commands,
+        &common_assets,
+        &mut maps,
+        ReferenceItem::Magnetite,
+        4,
+        node,
+    );
+    state.apply(&mut app.world);
+    build(&mut app, BDrill, node, IsoDirection::default());
+    run_ticks(&mut app, 20);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let drill = stats.get(node).unwrap();
+    // Nothing takes from the drill, so it fills each of its three outputs once
+    // and then has nowhere to put anything else.
+    assert_eq!(drill.items_out, 3);
+    assert!(drill.ticks_processing > 0);
+    assert!(drill.ticks_blocked > 0);
+}
+
+#[test]
+fn claw_fills_warehouse() {
+    let mut app = app();
+    let warehouse = IsoPos::origin();
+    let input = warehouse.offset_perp_direction(IsoDirection::PosA, 1);
+    let source = input.offset_b(2);
+    build(
+        &mut app,
+        BSmallWarehouse(ItemList::new()),
+        warehouse,
+        IsoDirection::PosA,
+    );
+    build_spawner(&mut app, source, 8);
+    build(
+        &mut app,
+        BClaw {
+            take_from: source,
+            filter: ItemFilter::Any,
+            priority: 0,
+        },
+        input,
+        IsoDirection::default(),
+    );
+    run_ticks(&mut app, 100);
+    let mut storages = app.world.query::<&Storage>();
+    let storage = storages.iter(&app.world).next().unwrap();
+    assert!(storage.count(&ReferenceItem::Magnetite.as_item()) > 0);
+}
+
+/// Runs a claw with `filter` from the output of a warehouse holding ten
+/// magnetite to a destroyer and returns how many items got destroyed.
+fn run_warehouse_claw(filter: ItemFilter) -> u64 {
+    let mut app = app();
+    let warehouse = IsoPos::origin();
+    let output = warehouse.offset_direction(IsoDirection::PosA, 2);
+    let sink = warehouse.offset_direction(IsoDirection::PosA, 6);
+    let mut items = ItemList::new();
+    items.add_bulk(ReferenceItem::Magnetite.as_item(), 10);
+    build(
+        &mut app,
+        BSmallWarehouse(items),
+        warehouse,
+        IsoDirection::PosA,
+    );
+    build(&mut app, BDestroyer, sink, IsoDirection::default());
+    build(
+        &mut app,
+        BClaw {
+            take_from: output,
+            filter,
+            priority: 0,
+        },
+        sink,
+        IsoDirection::default(),
+    );
+    run_ticks(&mut app, 600);
+    let mut storages = app.world.query::<&Storage>();
+    let storage = storages.iter(&app.world).next().unwrap();
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let destroyed = stats.get(sink).unwrap().items_in;
+    assert_eq!(
+        storage.count(&ReferenceItem::Magnetite.as_item()) as u64 + destroyed,
+        10
+    );
+    destroyed
+}
+
+#[test]
+fn claw_empties_warehouse() {
+    assert_eq!(run_warehouse_claw(ItemFilter::Any), 10);
+    let iron = ItemRule::Is(ReferenceItem::IronLump);
+    assert_eq!(run_warehouse_claw(ItemFilter::Allow(vec![iron])), 0);
+    // Claws set to one item pull it out of storage by name.
+    let only_magnetite = ItemFilter::Only(ReferenceItem::Magnetite);
+    assert_eq!(run_warehouse_claw(only_magnetite), 10);
+    let only_iron = ItemFilter::Only(ReferenceItem::IronLump);
+    assert_eq!(run_warehouse_claw(only_iron), 0);
+}
+
+/// Runs a claw with `filter` from a spawner of magnetite to a destroyer and
+/// returns how many items got destroyed.
+fn run_filtered_claw(filter: ItemFilter) -> u64 {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    spawner_to_destroyer(&mut app, source, sink, filter, 0);
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    stats.get(sink).unwrap().items_in
+}
+
+#[test]
+fn claw_filters_items() {
+    let magnetite = ItemRule::Is(ReferenceItem::Magnetite);
+    let impure = ItemRule::Contains(Element::Impurity);
+    let iron = ItemRule::Is(ReferenceItem::IronLump);
+    assert!(run_filtered_claw(ItemFilter::Allow(vec![magnetite])) > 0);
+    assert!(run_filtered_claw(ItemFilter::Allow(vec![iron, impure])) > 0);
+    assert!(run_filtered_claw(ItemFilter::Deny(vec![iron])) > 0);
+    // Anything the claw does not want stays in the spawner.
+    assert_eq!(run_filtered_claw(ItemFilter::Allow(vec![iron])), 0);
+    assert_eq!(run_filtered_claw(ItemFilter::Deny(vec![impure])), 0);
+}
+
+#[test]
+fn claw_moves_on_from_items_its_filter_rejects() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    let iron = ItemRule::Is(ReferenceItem::IronLump);
+    spawner_to_destroyer(&mut app, source, sink, ItemFilter::Allow(vec![iron]), 0);
+    // Long enough for the spawner to have its only item waiting.
+    run_ticks(&mut app, 20);
+    let mut positions = HashSet::new();
+    for _ in 0..20 {
+        run_ticks(&mut app, 1);
+        let claw = app.world.query::<&ClawLogic>().iter(&app.world).next();
+        let claw = claw.unwrap();
+        assert!(!claw.blocked());
+        positions.insert(claw.current_anim_tick());
+    }
+    assert!(positions.len() > 1);
+}
+
+#[test]
+fn claw_reattaches_to_rebuilt_destroyer() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    spawner_to_destroyer(&mut app, source, sink, ItemFilter::Any, 0);
+    run_ticks(&mut app, 100);
+    destroy(&mut app, sink);
+    run_ticks(&mut app, 100);
+    let claw = app.world.query::<&ClawLogic>().iter(&app.world).next();
+    assert!(!claw.unwrap().is_attached());
+    // Whatever the claw was holding went back or got thrown away, so only the
+    // item waiting in the spawner is left.
+    let items = app.world.query::<&Item>().iter(&app.world).count();
+    assert!(items <= 1);
+
+    build(&mut app, BDestroyer, sink, IsoDirection::default());
+    run_ticks(&mut app, 100);
+    let claw = app.world.query::<&ClawLogic>().iter(&app.world).next();
+    assert!(claw.unwrap().is_attached());
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    assert!(stats.get(sink).unwrap().items_in > 0);
+}
+
+#[test]
+fn claw_placement_is_checked() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let near = source.offset_a(2);
+    let far = source.offset_a(40);
+    let empty = source.offset_b(2);
+    for &position in &[source, near, far] {
+        build(&mut app, BDestroyer, position, IsoDirection::default());
+    }
+    run_ticks(&mut app, 1);
+    let mut state: SystemState<BuildingMaps> = SystemState::new(&mut app.world);
+    let maps = state.get_mut(&mut app.world);
+    let claw = BClaw {
+        take_from: source,
+        filter: ItemFilter::Any,
+        priority: 0,
+    };
+    assert!(claw.check_placement(near, &maps).is_ok());
+    assert_eq!(
+        claw.check_placement(source, &maps),
+        Err(ClawPlacementError::ZeroLength)
+    );
+    assert!(matches!(
+        claw.check_placement(far, &maps),
+        Err(ClawPlacementError::OutOfReach { .. })
+    ));
+    assert_eq!(
+        claw.check_placement(empty, &maps),
+        Err(ClawPlacementError::NoContainer(empty))
+    );
+}
+
+/// Runs two claws with the given priorities from a single slow spawner to
+/// their own destroyers and returns how many items each of them delivered.
+fn run_competing_claws(priorities: [u8; 2]) -> [u64; 2] {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sinks = [source.offset_a(2), source.offset_a(-2)];
+    build_spawner(&mut app, source, 40);
+    for (&sink, &priority) in sinks.iter().zip(priorities.iter()) {
+        build(&mut app, BDestroyer, sink, IsoDirection::default());
+        build(
+            &mut app,
+            BClaw {
+                take_from: source,
+                filter: ItemFilter::Any,
+                priority,
+            },
+            sink,
+            IsoDirection::default(),
+        );
+    }
+    run_ticks(&mut app, 800);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    [
+        stats.get(sinks[0]).unwrap().items_in,
+        stats.get(sinks[1]).unwrap().items_in,
+    ]
+}
+
+#[test]
+fn claws_take_turns() {
+    let [first, second] = run_competing_claws([0, 0]);
+    assert!(first > 0 && second > 0);
+    assert!((first as i64 - second as i64).abs() <= 1);
+}
+
+#[test]
+fn higher_priority_claws_go_first() {
+    assert_eq!(run_competing_claws([0, 1])[0], 0);
+    assert_eq!(run_competing_claws([2, 1])[1], 0);
+}
+
+#[test]
+fn claws_share_a_destination() {
+    let mut app = app();
+    let shared = IsoPos::origin();
+    let sources = [shared.offset_a(-2), shared.offset_b(2)];
+    build(&mut app, BDestroyer, shared, IsoDirection::default());
+    for &source in &sources {
+        build_spawner(&mut app, source, 1);
+        build(
+            &mut app,
+            BClaw {
+                take_from: source,
+                filter: ItemFilter::Any,
+                priority: 0,
+            },
+            shared,
+            IsoDirection::default(),
+        );
+    }
+    run_ticks(&mut app, 400);
+    let mut state: SystemState<BuildingMaps> = SystemState::new(&mut app.world);
+    let claws = state.get_mut(&mut app.world).claws.get(shared).cloned();
+    assert_eq!(claws.map(|claws| claws.len()), Some(2));
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    let made: Vec<u64> = sources
+        .iter()
+        .map(|&source| stats.get(source).unwrap().items_out)
+        .collect();
+    // Neither claw gets starved.
+    assert!(made.iter().all(|&count| count > 1));
+    // Everything ends up in the shared destroyer, apart from an item waiting
+    // in each spawner and one in each claw.
+    let destroyed = stats.get(shared).unwrap().items_in;
+    assert!(destroyed + 4 >= made.iter().sum::<u64>());
+}
+
+#[test]
+fn waypoint_claw_skips_stops_its_filter_rejects() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let picky_sink = source.offset_a(2);
+    let sink = source.offset_a(4);
+    build_spawner(&mut app, source, 8);
+    build(&mut app, BDestroyer, picky_sink, IsoDirection::default());
+    build(&mut app, BDestroyer, sink, IsoDirection::default());
+    let stop = |position, kind, filter| ClawStop {
+        position,
+        kind,
+        filter,
+    };
+    let iron = ItemRule::Is(ReferenceItem::IronLump);
+    build(
+        &mut app,
+        BWaypointClaw {
+            stops: vec![
+                stop(source, StopKind::Pick, ItemFilter::Any),
+                stop(picky_sink, StopKind::Drop, ItemFilter::Allow(vec![iron])),
+                stop(sink, StopKind::Drop, ItemFilter::Any),
+            ],
+            priority: 0,
+        },
+        sink,
+        IsoDirection::default(),
+    );
+    run_ticks(&mut app, 200);
+    let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+    assert_eq!(stats.get(picky_sink).unwrap().items_in, 0);
+    assert!(stats.get(sink).unwrap().items_in > 0);
+}
+
+#[test]
+fn paused_factory_only_moves_when_stepped() {
+    let mut app = app();
+    let source = IsoPos::origin();
+    let sink = source.offset_a(2);
+    spawner_to_destroyer(&mut app, source, sink, ItemFilter::Any, 0);
+    run_ticks(&mut app, 50);
+    let current_tick = |app: &App| {
+        app.world
+            .get_resource::<TickClock>()
+            .unwrap()
+            .current_tick()
+    };
+    let destroyed = |app: &App| {
+        let stats = app.world.get_resource::<ThroughputStats>().unwrap();
+        stats.get(sink).unwrap().items_in
+    };
+    let paused_at = current_tick(&app);
+    let destroyed_before = destroyed(&app);
+    let mut tick_clock = app.world.get_resource_mut::<TickClock>().unwrap();
+    tick_clock.toggle_paused();
+    run_ticks(&mut app, 100);
+    assert_eq!(current_tick(&app), paused_at);
+    assert_eq!(destroyed(&app), destroyed_before);
+
+    let mut tick_clock = app.world.get_resource_mut::<TickClock>().unwrap();
+    tick_clock.request_step();
+    run_ticks(&mut app, 10);
+    assert_eq!(current_tick(&app), paused_at + 1);
+
+    let mut tick_clock = app.world.get_resource_mut::<TickClock>().unwrap();
+    tick_clock.toggle_paused();
+    run_ticks(&mut app, 100);
+    assert_eq!(current_tick(&app), paused_at + 101);
+    assert!(destroyed(&app) > destroyed_before);
+}
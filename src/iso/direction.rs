@@ -1,8 +1,9 @@
 use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
 use super::*;
 
-#[derive(Clone, Copy, Component, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Component, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IsoDirection {
     PosA,
     NegC,
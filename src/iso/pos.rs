@@ -1,4 +1,5 @@
 use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
 use super::*;
 
@@ -10,7 +11,20 @@ use super::*;
 /// coordinates to uniquely describe a position, so we store x, y
 /// coordinates instead of A, B, C.
 #[make_constructor]
-#[derive(Clone, Copy, Component, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(
+    Clone,
+    Copy,
+    Component,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
 pub struct IsoPos {
     x: i32,
     /// +y points towards the top of the screen.
@@ -23,7 +37,7 @@ impl IsoPos {
     pub fn from_world_pos(pos: Vec2, snapping: Snapping) -> Self {
         if let Snapping::AlongLine { through, axis } = snapping {
             let centroid = through.centroid_pos();
-            let axis_unit = axis.unit_vec();
+            let axis_unit = offset_unit_vec(axis);
             let distance_along_axis = (pos - centroid).dot(axis_unit);
             return Self::from_world_pos(
                 centroid + axis_unit * distance_along_axis + Vec2::new(0.0, 0.01),
@@ -236,6 +250,19 @@ impl IsoPos {
         )
     }
 
+    /// Returns how far along `axis` this coordinate has to be moved to reach
+    /// `other`, or None if `other` is not on the line through this
+    /// coordinate parallel to `axis`.
+    pub fn steps_along(self, axis: IsoAxis, other: Self) -> Option<i32> {
+        // Every step along an axis moves the centroid half an edge along it.
+        let delta = (other.centroid_pos() - self.centroid_pos()).dot(offset_unit_vec(axis));
+        let estimate = (delta / (GRID_EDGE_LENGTH * 0.5)).round() as i32;
+        [estimate, estimate - 1, estimate + 1]
+            .iter()
+            .copied()
+            .find(|&steps| self.offset_axis(axis, steps) == other)
+    }
+
     /// Move the coordinate along the axis perpendicular to +A.
     pub const fn offset_perp_a(self, offset: i32) -> Self {
         // Just like the offset_a algorithm, but rotated 90 degrees.
@@ -356,3 +383,12 @@ impl IsoPos {
         (self.x, self.y)
     }
 }
+
+/// The direction offset_axis moves in. This is not always the direction
+/// IsoAxis::unit_vec points in, which is the way a building facing along the
+/// axis is drawn. Two steps are measured so that the zigzag between left and
+/// right pointing cells cancels out.
+fn offset_unit_vec(axis: IsoAxis) -> Vec2 {
+    let origin = IsoPos::origin();
+    (origin.offset_axis(axis, 2).centroid_pos() - origin.centroid_pos()).normalize()
+}
@@ -75,7 +75,25 @@ macro_rules! map_newtype {
 map_newtype!(ConveyorMap, Entity);
 map_newtype!(ItemContainerMap, Entity);
 map_newtype!(BuildingMap, Entity);
-map_newtype!(ClawMap, Entity);
+// Several claws can end at the same position, so each position keeps every
+// claw ending there in the order they were built.
+map_newtype!(ClawMap, Vec<Entity>);
+
+impl ClawMap {
+    pub fn add(&mut self, pos: IsoPos, claw: Entity) {
+        self.0.contents.entry(pos).or_default().push(claw);
+    }
+
+    /// Forgets `claw`, leaving any other claws ending at `pos` in place.
+    pub fn remove(&mut self, pos: IsoPos, claw: Entity) {
+        if let Some(claws) = self.0.contents.get_mut(&pos) {
+            claws.retain(|&other| other != claw);
+            if claws.is_empty() {
+                self.0.contents.remove(&pos);
+            }
+        }
+    }
+}
 
 pub struct Plug;
 
@@ -55,3 +55,38 @@ fn perp_axis_offsets() {
     assert_eq!(IsoPos::new(1, 0).offset_perp_c(2), IsoPos::new(2, 3));
     assert_eq!(IsoPos::new(1, 0).offset_perp_c(-2), IsoPos::new(0, -3));
 }
+
+#[test]
+fn steps_along_axes() {
+    for &start in &[IsoPos::new(0, 0), IsoPos::new(1, 0), IsoPos::new(-3, 2)] {
+        for &axis in &[IsoAxis::A, IsoAxis::B, IsoAxis::C] {
+            for steps in -7..=7 {
+                let end = start.offset_axis(axis, steps);
+                assert_eq!(start.steps_along(axis, end), Some(steps));
+            }
+        }
+    }
+    assert_eq!(
+        IsoPos::origin().steps_along(IsoAxis::A, IsoPos::new(1, 0)),
+        None
+    );
+    assert_eq!(
+        IsoPos::origin().steps_along(IsoAxis::B, IsoPos::new(0, 4)),
+        None
+    );
+}
+
+#[test]
+fn snapping_along_line_stays_on_line() {
+    let through = IsoPos::new(1, 0);
+    for &axis in &[IsoAxis::A, IsoAxis::B, IsoAxis::C] {
+        for steps in -6..=6 {
+            let target = through.offset_axis(axis, steps);
+            let snapped = IsoPos::from_world_pos(
+                target.centroid_pos(),
+                Snapping::AlongLine { through, axis },
+            );
+            assert_eq!(snapped, target);
+        }
+    }
+}
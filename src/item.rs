@@ -5,10 +5,12 @@ mod container;
 mod container_debug;
 mod element;
 mod reference_item;
+mod rule;
+mod tests;
 
 use bevy::prelude::*;
 
-pub use self::{animator::*, base::*, container::*, element::*, reference_item::*};
+pub use self::{animator::*, base::*, container::*, element::*, reference_item::*, rule::*};
 use crate::prelude::*;
 
 pub fn spawn_item(
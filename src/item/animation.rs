@@ -1,64 +1,237 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub(super) enum ItemAnim {
     Stay(Vec2),
     Lerp(LerpAnim),
+    Path(PathAnim),
+    /// Not drawn at all, like when the item is underground.
+    Hidden(Vec2),
 }
 
 impl ItemAnim {
-    pub fn new_lerp(from: Vec2, to: Vec2, total_ticks: u8, remaining_ticks: u8) -> Self {
+    /// Creates an animation which starts during `start_tick` and lasts
+    /// `total_ticks` ticks.
+    pub fn new_lerp(from: Vec2, to: Vec2, total_ticks: u8, start_tick: u64) -> Self {
         Self::Lerp(LerpAnim {
             from,
             to,
-            total_ticks,
-            remaining_ticks,
+            timing: AnimTiming {
+                total_ticks,
+                start_tick,
+            },
         })
     }
 
+    /// Like `new_lerp`, but follows `segments` one after another instead of
+    /// going straight to the end. The item moves at the same speed along the
+    /// whole path.
+    pub fn new_path(
+        from: Vec2,
+        segments: Vec<PathSegment>,
+        total_ticks: u8,
+        start_tick: u64,
+    ) -> Self {
+        Self::Path(PathAnim::new(from, segments, total_ticks, start_tick))
+    }
+
     pub fn evaluate(&mut self, tick_clock: &TickClock) -> Vec2 {
         match self {
             ItemAnim::Stay(pos) => pos.clone(),
             ItemAnim::Lerp(anim) => anim.evaluate(tick_clock),
+            ItemAnim::Path(anim) => anim.point_at(anim.timing.progress(tick_clock)),
+            ItemAnim::Hidden(pos) => pos.clone(),
         }
     }
 
+    pub fn is_visible(&self) -> bool {
+        !matches!(self, ItemAnim::Hidden(..))
+    }
+
     pub fn rest_position(&self) -> Vec2 {
         match self {
             ItemAnim::Stay(pos) => pos.clone(),
             ItemAnim::Lerp(LerpAnim { to, .. }) => to.clone(),
+            ItemAnim::Path(anim) => anim.rest_position(),
+            ItemAnim::Hidden(pos) => pos.clone(),
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct AnimTiming {
+    total_ticks: u8,
+    start_tick: u64,
+}
+
+impl AnimTiming {
+    fn progress(&self, tick_clock: &TickClock) -> f32 {
+        // Counting ticks instead of decrementing a timer once per frame keeps
+        // this correct when several ticks run in a single frame. Animations
+        // from a loaded save can start ahead of the clock, and those have not
+        // started moving yet.
+        let current_anim_tick = tick_clock.current_tick().saturating_sub(self.start_tick) as f32;
+        let sub_tick_progress = tick_clock.get_tick_progress();
+        ((current_anim_tick + sub_tick_progress) / self.total_ticks as f32).min(1.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub(super) struct LerpAnim {
     from: Vec2,
     to: Vec2,
-    total_ticks: u8,
-    remaining_ticks: u8,
+    timing: AnimTiming,
 }
 
 impl LerpAnim {
     fn evaluate(&mut self, tick_clock: &TickClock) -> Vec2 {
-        self.maybe_advance_remaining_ticks(tick_clock);
-        let progress = self.progress(tick_clock);
+        let progress = self.timing.progress(tick_clock);
         self.from.lerp(self.to, progress)
     }
+}
 
-    fn maybe_advance_remaining_ticks(&mut self, tick_clock: &TickClock) {
-        if tick_clock.is_tick_this_frame() && self.remaining_ticks > 0 {
-            self.remaining_ticks -= 1;
+/// One piece of a `PathAnim`, which starts wherever the previous one ended.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(super) enum PathSegment {
+    Line {
+        to: Vec2,
+    },
+    /// Goes around `center` the short way, ending at `to`.
+    Arc {
+        center: Vec2,
+        to: Vec2,
+    },
+}
+
+impl PathSegment {
+    /// Returns an arc that leaves `from` heading in `heading` and ends at
+    /// `to`, or a straight line if `to` is dead ahead of `from`.
+    pub fn arc_from_heading(from: Vec2, heading: Vec2, to: Vec2) -> Self {
+        let normal = heading.normalize().perp();
+        let delta = to - from;
+        let bend = normal.dot(delta);
+        if bend.abs() <= 1e-4 * delta.length() {
+            return Self::Line { to };
         }
+        // The center is the point on the normal which is as far from `to` as
+        // it is from `from`.
+        let center = from + normal * (delta.length_squared() / (2.0 * bend));
+        Self::Arc { center, to }
     }
 
-    fn progress(&mut self, tick_clock: &TickClock) -> f32 {
-        let current_anim_tick = self.current_anim_tick() as f32;
-        let sub_tick_progress = tick_clock.get_tick_progress();
-        (current_anim_tick + sub_tick_progress) / self.total_ticks as f32
+    fn end(&self) -> Vec2 {
+        match *self {
+            Self::Line { to } | Self::Arc { to, .. } => to,
+        }
+    }
+
+    /// The signed angle an arc turns through, which is never more than half
+    /// a turn.
+    fn sweep(from: Vec2, center: Vec2, to: Vec2) -> f32 {
+        let (from, to) = (from - center, to - center);
+        from.perp_dot(to).atan2(from.dot(to))
     }
 
-    fn current_anim_tick(&mut self) -> u8 {
-        self.total_ticks - self.remaining_ticks - 1
+    fn length(&self, from: Vec2) -> f32 {
+        match *self {
+            Self::Line { to } => from.distance(to),
+            Self::Arc { center, to } => {
+                let radius = (from.distance(center) + to.distance(center)) * 0.5;
+                Self::sweep(from, center, to).abs() * radius
+            }
+        }
+    }
+
+    fn point_at(&self, from: Vec2, progress: f32) -> Vec2 {
+        match *self {
+            Self::Line { to } => from.lerp(to, progress),
+            Self::Arc { center, to } => {
+                let sweep = Self::sweep(from, center, to);
+                let from_offset = from - center;
+                let radius =
+                    from_offset.length() + (to.distance(center) - from_offset.length()) * progress;
+                let angle = from_offset.y.atan2(from_offset.x) + sweep * progress;
+                center + Vec2::new(angle.cos(), angle.sin()) * radius
+            }
+        }
+    }
+}
+
+/// Returns a path from `from` through each of `waypoints` in turn, with the
+/// corners at all but the last one rounded off.
+pub(super) fn rounded_path(from: Vec2, waypoints: &[Vec2]) -> Vec<PathSegment> {
+    let mut segments = Vec::new();
+    let mut start = from;
+    for (index, &corner) in waypoints.iter().enumerate() {
+        let next = waypoints.get(index + 1).copied().unwrap_or(corner);
+        let in_dir = (corner - start).normalize_or_zero();
+        let out_dir = (next - corner).normalize_or_zero();
+        if in_dir == Vec2::ZERO || out_dir == Vec2::ZERO {
+            segments.push(PathSegment::Line { to: corner });
+            start = corner;
+            continue;
+        }
+        // Each corner may use up to half of the straight sections on either
+        // side of it, so neighbouring corners never overlap.
+        let cut = start.distance(corner).min(corner.distance(next)) * 0.5;
+        let arc_start = corner - in_dir * cut;
+        let arc_end = corner + out_dir * cut;
+        segments.push(PathSegment::Line { to: arc_start });
+        segments.push(PathSegment::arc_from_heading(arc_start, in_dir, arc_end));
+        start = arc_end;
+    }
+    segments
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(super) struct PathAnim {
+    from: Vec2,
+    segments: Vec<PathSegment>,
+    timing: AnimTiming,
+}
+
+impl PathAnim {
+    pub(super) fn new(
+        from: Vec2,
+        segments: Vec<PathSegment>,
+        total_ticks: u8,
+        start_tick: u64,
+    ) -> Self {
+        Self {
+            from,
+            segments,
+            timing: AnimTiming {
+                total_ticks,
+                start_tick,
+            },
+        }
+    }
+
+    pub(super) fn rest_position(&self) -> Vec2 {
+        self.segments.last().map_or(self.from, PathSegment::end)
+    }
+
+    /// Returns where an item `progress` of the way along the path is.
+    pub(super) fn point_at(&self, progress: f32) -> Vec2 {
+        let mut starts = Vec::with_capacity(self.segments.len());
+        let mut lengths = Vec::with_capacity(self.segments.len());
+        let mut start = self.from;
+        for segment in &self.segments {
+            starts.push(start);
+            lengths.push(segment.length(start));
+            start = segment.end();
+        }
+        let total: f32 = lengths.iter().sum();
+        let mut remaining = total * progress;
+        for ((segment, &start), &length) in self.segments.iter().zip(&starts).zip(&lengths) {
+            if remaining <= length && length > 0.0 {
+                return segment.point_at(start, remaining / length);
+            }
+            remaining -= length;
+        }
+        self.rest_position()
     }
 }
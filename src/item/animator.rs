@@ -1,9 +1,15 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use super::{animation::ItemAnim, ItemContainerAlignment};
+use super::{
+    animation::{self, ItemAnim},
+    ItemContainerAlignment,
+};
 use crate::prelude::*;
 
-#[derive(Component)]
+/// Saving an animator along with the clock it was created with lets an item
+/// carry on moving after loading.
+#[derive(Clone, Component, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ItemAnimator {
     anim: ItemAnim,
 }
@@ -24,13 +30,37 @@ impl ItemAnimator {
         pos: IsoPos,
         alignment: ItemContainerAlignment,
         anim_duration: u8,
+        tick_clock: &TickClock,
     ) {
         let target_pos = alignment.get_item_pos(pos);
         self.anim = ItemAnim::new_lerp(
             self.current_rest_position(),
             target_pos,
             anim_duration,
+            tick_clock.current_tick(),
+        );
+    }
+
+    /// Like `anim_to_container`, but for items coming from a neighbouring
+    /// belt at `from` that runs along a different axis. Instead of cutting the
+    /// corner, the item keeps going until it crosses into the container and
+    /// then curves around onto the container's axis.
+    pub fn anim_around_corner(
+        &mut self,
+        from: IsoPos,
+        pos: IsoPos,
+        alignment: ItemContainerAlignment,
+        anim_duration: u8,
+        tick_clock: &TickClock,
+    ) {
+        let start_pos = self.current_rest_position();
+        let border = (from.centroid_pos() + pos.centroid_pos()) * 0.5;
+        let target_pos = alignment.get_item_pos(pos);
+        self.anim = ItemAnim::new_path(
+            start_pos,
+            animation::rounded_path(start_pos, &[border, target_pos]),
             anim_duration,
+            tick_clock.current_tick(),
         );
     }
 
@@ -41,14 +71,22 @@ impl ItemAnimator {
     pub fn anim_stationary_exact(&mut self, pos: Vec2) {
         self.anim = ItemAnim::Stay(pos);
     }
+
+    /// Stops drawing the item until it is given another animation.
+    pub fn anim_hidden(&mut self) {
+        self.anim = ItemAnim::Hidden(self.current_rest_position());
+    }
 }
 
 pub(super) fn animate_items(
     tick_clock: Res<TickClock>,
-    mut items: Query<(&mut Transform, &mut ItemAnimator)>,
+    mut items: Query<(&mut Transform, Option<&mut Visibility>, &mut ItemAnimator)>,
 ) {
-    for (mut transform, mut item) in items.iter_mut() {
+    for (mut transform, visibility, mut item) in items.iter_mut() {
         let pos = item.anim.evaluate(&*tick_clock);
         transform.translation = (pos, 0.1).into();
+        if let Some(mut visibility) = visibility {
+            visibility.is_visible = item.anim.is_visible();
+        }
     }
 }
@@ -1,8 +1,11 @@
 use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
 use super::{Element, ReferenceItem};
 
-#[derive(Clone, Component, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Clone, Component, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub struct Item {
     elements: Vec<Element>,
 }
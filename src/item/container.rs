@@ -18,6 +18,11 @@ impl ItemContainerAlignment {
     }
 }
 
+/// Marks containers that buildings put finished items into, which conveyors
+/// starting in front of them may take items out of.
+#[derive(Component)]
+pub struct OutputContainer;
+
 #[derive(Component)]
 pub struct ItemContainer {
     alignment: ItemContainerAlignment,
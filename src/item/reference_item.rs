@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 use super::{Element, Item};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ReferenceItem {
     Magnetite,
     PureFerrous,
@@ -0,0 +1,154 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Element, Item, ReferenceItem};
+
+const ELEMENTS: [Element; 3] = [Element::Animus, Element::Ferrous, Element::Impurity];
+
+/// A question about what an item is made of, which buildings use to decide
+/// what to do with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemRule {
+    /// It is exactly this item.
+    Is(ReferenceItem),
+    /// It has at least one of this element in it. Checking for
+    /// `Element::Impurity` finds anything that still needs purifying.
+    Contains(Element),
+    /// Its mass is more than this many kilograms.
+    HeavierThan(u32),
+    /// Its volume is more than this many liters.
+    LargerThan(u32),
+}
+
+impl Default for ItemRule {
+    fn default() -> Self {
+        Self::Is(ReferenceItem::Magnetite)
+    }
+}
+
+impl ItemRule {
+    pub fn matches(&self, item: &Item) -> bool {
+        match *self {
+            Self::Is(known) => *item == known.as_item(),
+            Self::Contains(element) => item.as_elements().contains(&element),
+            Self::HeavierThan(mass) => item.mass() > mass,
+            Self::LargerThan(volume) => item.volume() > volume,
+        }
+    }
+
+    /// Switches to the next kind of rule, for cycling through them in the UI.
+    pub fn next_kind(self) -> Self {
+        match self {
+            Self::Is(..) => Self::Contains(Element::Impurity),
+            Self::Contains(..) => Self::HeavierThan(10),
+            Self::HeavierThan(..) => Self::LargerThan(5),
+            Self::LargerThan(..) => Self::Is(ReferenceItem::Magnetite),
+        }
+    }
+
+    /// Moves whatever the rule checks for `steps` places along, wrapping
+    /// around when picking an item or element.
+    pub fn adjusted(self, steps: i32) -> Self {
+        let cycle =
+            |index: usize, len: usize| (index as i32 + steps).rem_euclid(len as i32) as usize;
+        match self {
+            Self::Is(known) => {
+                let all = ReferenceItem::all();
+                let index = all.iter().position(|&other| other == known).unwrap();
+                Self::Is(all[cycle(index, all.len())])
+            }
+            Self::Contains(element) => {
+                let index = ELEMENTS.iter().position(|&other| other == element).unwrap();
+                Self::Contains(ELEMENTS[cycle(index, ELEMENTS.len())])
+            }
+            Self::HeavierThan(mass) => Self::HeavierThan((mass as i32 + steps).max(0) as u32),
+            Self::LargerThan(volume) => Self::LargerThan((volume as i32 + steps).max(0) as u32),
+        }
+    }
+}
+
+impl Display for ItemRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Is(known) => write!(f, "is {:?}", known),
+            Self::Contains(element) => write!(f, "contains {:?}", element),
+            Self::HeavierThan(mass) => write!(f, "heavier than {}kg", mass),
+            Self::LargerThan(volume) => write!(f, "larger than {}L", volume),
+        }
+    }
+}
+
+/// Decides which items something like a claw will handle.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemFilter {
+    Any,
+    /// Exactly this one item. Claws taking from a warehouse pull this item
+    /// out of storage by name instead of searching for one that matches.
+    Only(ReferenceItem),
+    /// Only items matching at least one of the rules.
+    Allow(Vec<ItemRule>),
+    /// Everything except items matching any of the rules.
+    Deny(Vec<ItemRule>),
+}
+
+impl Default for ItemFilter {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl ItemFilter {
+    pub fn allows(&self, item: &Item) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Only(known) => *item == known.as_item(),
+            Self::Allow(rules) => rules.iter().any(|rule| rule.matches(item)),
+            Self::Deny(rules) => !rules.iter().any(|rule| rule.matches(item)),
+        }
+    }
+
+    /// Adds a rule to the list, turning a filter that allows anything into
+    /// one that only allows items matching the rule.
+    pub fn add_rule(&mut self, rule: ItemRule) {
+        match self {
+            Self::Any => *self = Self::Allow(vec![rule]),
+            &mut Self::Only(known) => {
+                *self = Self::Allow(vec![ItemRule::Is(known)]);
+                self.add_rule(rule);
+            }
+            Self::Allow(rules) | Self::Deny(rules) => {
+                if !rules.contains(&rule) {
+                    rules.push(rule);
+                }
+            }
+        }
+    }
+
+    /// Switches between allowing and denying the items matching the rules.
+    pub fn invert(&mut self) {
+        *self = match std::mem::take(self) {
+            Self::Any => Self::Any,
+            Self::Only(known) => Self::Deny(vec![ItemRule::Is(known)]),
+            Self::Allow(rules) => Self::Deny(rules),
+            Self::Deny(rules) => Self::Allow(rules),
+        };
+    }
+}
+
+impl Display for ItemFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (prefix, rules) = match self {
+            Self::Any => return write!(f, "any item"),
+            Self::Only(known) => return write!(f, "exactly {:?}", known),
+            Self::Allow(rules) => ("only", rules),
+            Self::Deny(rules) => ("except", rules),
+        };
+        write!(f, "{}", prefix)?;
+        for (index, rule) in rules.iter().enumerate() {
+            let separator = if index == 0 { " " } else { " or " };
+            write!(f, "{}{}", separator, rule)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+#![cfg(test)]
+
+use super::{
+    animation::{rounded_path, PathAnim},
+    Element, ItemFilter, ItemRule, ReferenceItem,
+};
+use crate::prelude::*;
+
+fn close(a: Vec2, b: Vec2) -> bool {
+    a.distance(b) < 1e-3
+}
+
+/// Samples `steps + 1` evenly spaced points along a path.
+fn sample(from: Vec2, waypoints: &[Vec2], steps: usize) -> Vec<Vec2> {
+    let anim = PathAnim::new(from, rounded_path(from, waypoints), 1, 0);
+    (0..=steps)
+        .map(|step| anim.point_at(step as f32 / steps as f32))
+        .collect()
+}
+
+#[test]
+fn path_starts_and_ends_at_its_ends() {
+    let from = Vec2::new(0.0, 0.0);
+    let waypoints = [
+        Vec2::new(2.0, 0.0),
+        Vec2::new(3.0, 2.0),
+        Vec2::new(1.0, 4.0),
+    ];
+    let points = sample(from, &waypoints, 10);
+    assert!(close(points[0], from));
+    assert!(close(*points.last().unwrap(), waypoints[2]));
+}
+
+#[test]
+fn straight_path_is_a_lerp() {
+    let from = Vec2::new(1.0, 1.0);
+    let to = Vec2::new(5.0, 3.0);
+    for (step, point) in sample(from, &[to], 4).into_iter().enumerate() {
+        assert!(close(point, from.lerp(to, step as f32 / 4.0)));
+    }
+}
+
+#[test]
+fn corners_are_rounded() {
+    let from = Vec2::new(0.0, 0.0);
+    let corner = Vec2::new(2.0, 0.0);
+    let to = Vec2::new(2.0, 2.0);
+    let points = sample(from, &[corner, to], 20);
+    // Halfway along, the item is going around the corner, not cutting across
+    // it or sitting right on it.
+    let halfway = points[10];
+    assert!(halfway.distance(corner) < halfway.distance(Vec2::new(1.0, 1.0)));
+    assert!(halfway.distance(corner) > 0.1);
+    // The item never leaves the box the corner sits in.
+    for point in &points {
+        assert!(point.x > -1e-3 && point.x < 2.0 + 1e-3);
+        assert!(point.y > -1e-3 && point.y < 2.0 + 1e-3);
+    }
+}
+
+#[test]
+fn paths_are_followed_at_a_steady_speed() {
+    let from = Vec2::new(0.0, 0.0);
+    let waypoints = [Vec2::new(2.0, 0.0), Vec2::new(1.0, 2.0)];
+    let points = sample(from, &waypoints, 40);
+    let steps: Vec<f32> = points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .collect();
+    let longest = steps.iter().cloned().fold(0.0, f32::max);
+    let shortest = steps.iter().cloned().fold(f32::INFINITY, f32::min);
+    // Steps crossing from a line onto an arc are slightly shorter, since the
+    // item curves within them.
+    assert!(shortest > longest * 0.9);
+}
+
+#[test]
+fn rules_match_by_composition() {
+    let magnetite = ReferenceItem::Magnetite.as_item();
+    let iron = ReferenceItem::IronLump.as_item();
+    assert!(ItemRule::Is(ReferenceItem::Magnetite).matches(&magnetite));
+    assert!(!ItemRule::Is(ReferenceItem::Magnetite).matches(&iron));
+    assert!(ItemRule::Contains(Element::Impurity).matches(&magnetite));
+    assert!(!ItemRule::Contains(Element::Impurity).matches(&iron));
+    assert!(ItemRule::Contains(Element::Ferrous).matches(&iron));
+    // Magnetite weighs 20kg and iron lumps 16kg.
+    assert!(ItemRule::HeavierThan(16).matches(&magnetite));
+    assert!(!ItemRule::HeavierThan(16).matches(&iron));
+    // Magnetite takes up 7L and iron lumps 4L.
+    assert!(ItemRule::LargerThan(4).matches(&magnetite));
+    assert!(!ItemRule::LargerThan(4).matches(&iron));
+}
+
+#[test]
+fn rules_cycle_through_choices() {
+    let rule = ItemRule::Is(ReferenceItem::Magnetite);
+    let mut adjusted = rule;
+    for _ in 0..ReferenceItem::all().len() {
+        adjusted = adjusted.adjusted(1);
+    }
+    assert_eq!(adjusted, rule);
+    assert_eq!(rule.adjusted(-1).adjusted(1), rule);
+    assert_eq!(
+        ItemRule::HeavierThan(0).adjusted(-1),
+        ItemRule::HeavierThan(0)
+    );
+    let mut kind = rule.next_kind();
+    for _ in 0..10 {
+        if kind == rule {
+            return;
+        }
+        kind = kind.next_kind();
+    }
+    panic!("Never got back to the first kind of rule");
+}
+
+#[test]
+fn filters_are_edited_a_rule_at_a_time() {
+    let magnetite = ReferenceItem::Magnetite.as_item();
+    let iron = ReferenceItem::IronLump.as_item();
+    let mut filter = ItemFilter::Any;
+    assert!(filter.allows(&magnetite) && filter.allows(&iron));
+    filter.add_rule(ItemRule::Is(ReferenceItem::IronLump));
+    filter.add_rule(ItemRule::Is(ReferenceItem::IronLump));
+    assert_eq!(
+        filter,
+        ItemFilter::Allow(vec![ItemRule::Is(ReferenceItem::IronLump)])
+    );
+    assert!(!filter.allows(&magnetite) && filter.allows(&iron));
+    filter.invert();
+    assert!(filter.allows(&magnetite) && !filter.allows(&iron));
+    assert_eq!(filter.to_string(), "except is IronLump");
+}
+
+#[test]
+fn only_filter_allows_one_item() {
+    let magnetite = ReferenceItem::Magnetite.as_item();
+    let iron = ReferenceItem::IronLump.as_item();
+    let mut filter = ItemFilter::Only(ReferenceItem::IronLump);
+    assert!(!filter.allows(&magnetite) && filter.allows(&iron));
+    assert_eq!(filter.to_string(), "exactly IronLump");
+    filter.add_rule(ItemRule::Is(ReferenceItem::Magnetite));
+    assert!(filter.allows(&magnetite) && filter.allows(&iron));
+    filter.invert();
+    assert!(!filter.allows(&magnetite) && !filter.allows(&iron));
+}
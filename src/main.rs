@@ -1,11 +1,13 @@
 mod assets;
 mod buildable;
 mod common;
+mod headless;
 pub mod iso;
 mod item;
 pub mod mini_rand;
 pub mod prelude;
 pub mod resource_nodes;
+mod save;
 mod ui;
 
 use bevy::prelude::*;
@@ -18,8 +20,14 @@ use buildable::{
 };
 use item::ReferenceItem;
 use prelude::*;
+use resource_nodes::ResourceNodeSeed;
 
-fn test_scene(mut commands: Commands, common_assets: Res<CommonAssets>, mut maps: BuildingMaps) {
+fn test_scene(
+    mut commands: Commands,
+    common_assets: Res<CommonAssets>,
+    mut maps: BuildingMaps,
+    seed: Res<ResourceNodeSeed>,
+) {
     let mut ctx = BuildingContext {
         commands: &mut commands,
         position: IsoPos::default(),
@@ -57,35 +65,69 @@ fn test_scene(mut commands: Commands, common_assets: Res<CommonAssets>, mut maps
     items.add_bulk(ReferenceItem::PureAnimus.as_item(), 300);
     spawn_buildable(Box::new(BSmallWarehouse(items)), &mut ctx, &mut maps);
 
-    for x in -10..10 {
-        for y in -10..10 {
-            resource_nodes::spawn_resource_node_for_chunk(
-                &mut commands,
-                &common_assets,
-                &mut maps,
-                x,
-                y,
-            );
-        }
+    resource_nodes::spawn_resource_nodes(&mut commands, &common_assets, &mut maps, *seed);
+}
+
+/// Returns the argument following `flag`, if `flag` was passed.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    Some(
+        args.get(index + 1)
+            .unwrap_or_else(|| panic!("{} requires a value", flag)),
+    )
+}
+
+/// --load <path> starts with a saved factory instead of the test scene.
+fn add_scene(app: &mut App, args: &[String]) {
+    if let Some(path) = arg_value(args, "--load") {
+        let save = save::read(path).expect("Failed to read save file");
+        app.add_startup_system(
+            (move |world: &mut World| save::load(world, &save).expect("Failed to load save file"))
+                .exclusive_system(),
+        );
+    } else {
+        app.add_startup_system(test_scene.system());
     }
 }
 
 fn main() {
-    App::new()
-        .insert_resource(WindowDescriptor {
-            width: 600.0,
-            height: 500.0,
-            ..Default::default()
-        })
-        .add_plugins(DefaultPlugins)
-        .add_plugin(bevy_obj::ObjPlugin)
-        .add_plugin(iso::Plug)
-        .add_plugin(common::Plug)
-        .add_plugin(assets::Plug)
-        .add_plugin(ui::Plug)
-        .add_plugin(buildable::Plug)
-        .add_plugin(item::Plug)
-        .add_plugin(resource_nodes::Plug)
-        .add_startup_system(test_scene.system())
-        .run();
+    let args: Vec<String> = std::env::args().collect();
+    // --headless <ticks> runs the scene for that many ticks without opening a
+    // window.
+    if let Some(ticks) = arg_value(&args, "--headless") {
+        let ticks = ticks
+            .parse()
+            .expect("--headless requires a number of ticks");
+        let mut app = headless::app();
+        add_scene(&mut app, &args);
+        headless::run_ticks(&mut app, ticks);
+        return;
+    }
+
+    let mut app = App::new();
+    app.insert_resource(WindowDescriptor {
+        width: 600.0,
+        height: 500.0,
+        ..Default::default()
+    })
+    .add_plugins(DefaultPlugins)
+    .add_plugin(bevy_obj::ObjPlugin)
+    .add_plugin(iso::Plug)
+    .add_plugin(common::Plug)
+    .add_plugin(assets::Plug)
+    .add_plugin(ui::Plug)
+    .add_plugin(buildable::Plug)
+    .add_plugin(item::Plug)
+    .add_plugin(resource_nodes::Plug)
+    .add_plugin(save::Plug);
+    // --record <path> records the session so that it can be played back
+    // later with --replay <path>, which needs the same scene (or --load) that
+    // it was recorded with.
+    if let Some(path) = arg_value(&args, "--replay") {
+        app.insert_resource(ui::Replay::read(path).expect("Failed to read replay file"));
+    } else if let Some(path) = arg_value(&args, "--record") {
+        app.insert_resource(ui::Replay::record_to(path).expect("Failed to create replay file"));
+    }
+    add_scene(&mut app, &args);
+    app.run();
 }
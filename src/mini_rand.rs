@@ -7,10 +7,16 @@ const SEED: u64 = 0;
 /// This works best on Copy types. It will treat two references to the same
 /// thing as different.
 pub fn rand<D: Sized, S, T: FromRandom<S>>(input: D, spec: S) -> T {
+    rand_with_seed(SEED, input, spec)
+}
+
+/// Like rand(), but different seeds give different results for the same
+/// input.
+pub fn rand_with_seed<D: Sized, S, T: FromRandom<S>>(seed: u64, input: D, spec: S) -> T {
     let ptr = (&input) as *const D;
     let as_bytes =
         unsafe { std::slice::from_raw_parts(ptr as *const u8, std::mem::size_of::<D>()) };
-    let mut hash = wyhash(as_bytes, SEED);
+    let mut hash = wyhash(as_bytes, seed);
     let mut seeds = vec![0; T::num_seeds()];
     for seed_index in 0..T::num_seeds() {
         seeds[seed_index] = wyhash::wyrng(&mut hash);
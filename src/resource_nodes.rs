@@ -1,9 +1,16 @@
 use bevy::prelude::*;
 
-use crate::{buildable::BuildingMaps, item::ReferenceItem, map_newtype, prelude::*};
+use crate::{
+    buildable::BuildingMaps, item::ReferenceItem, map_newtype, mini_rand::rand_with_seed,
+    prelude::*,
+};
 
 map_newtype!(ResourceNodeMap, ResourceNode);
 
+/// Decides where resource nodes are placed in the world.
+#[derive(Clone, Copy, Default)]
+pub struct ResourceNodeSeed(pub u64);
+
 #[derive(Clone, Component, Debug, PartialEq, Eq, Hash)]
 pub struct ResourceNode {
     pub of: ReferenceItem,
@@ -39,14 +46,29 @@ pub fn spawn_resource_node(
         .insert(pos);
 }
 
+pub fn spawn_resource_nodes(
+    commands: &mut Commands,
+    common_assets: &CommonAssets,
+    maps: &mut BuildingMaps,
+    seed: ResourceNodeSeed,
+) {
+    for x in -10..10 {
+        for y in -10..10 {
+            spawn_resource_node_for_chunk(commands, common_assets, maps, seed, x, y);
+        }
+    }
+}
+
 pub fn spawn_resource_node_for_chunk(
     commands: &mut Commands,
     common_assets: &CommonAssets,
     maps: &mut BuildingMaps,
+    seed: ResourceNodeSeed,
     x: i32,
     y: i32,
 ) {
-    let (dx, dy, of, rate): (i32, i32, u8, u8) = rand((x, y), (0..96, 0..96, 0..255, 1..255));
+    let (dx, dy, of, rate): (i32, i32, u8, u8) =
+        rand_with_seed(seed.0, (x, y), (0..96, 0..96, 0..255, 1..255));
     let x = x * 110 + dx;
     let y = y * 110 + dy;
     let pos = IsoPos::new(x, y);
@@ -64,6 +86,7 @@ pub struct Plug;
 
 impl Plugin for Plug {
     fn build(&self, app: &mut App) {
-        app.insert_resource(ResourceNodeMap::default());
+        app.insert_resource(ResourceNodeMap::default())
+            .insert_resource(ResourceNodeSeed::default());
     }
 }
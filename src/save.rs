@@ -0,0 +1,587 @@
+//! Writes whole factories to disk and reads them back. Loading despawns
+//! everything that was built, then rebuilds it from scratch with
+//! spawn_buildable() so that every map layer is filled in the same way it
+//! would be if the player had placed everything by hand.
+
+mod tests;
+
+use std::{collections::HashMap, fs, path::Path};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buildable::{
+        claw::ClawLogic,
+        conveyor::{ConveyorLogic, SplitterLogic, UndergroundEntrance},
+        machine::MachineLogic,
+        spawn_buildable,
+        spawner::SpawnerLogic,
+        storage::{ItemList, Storage},
+        BuildableRegistry, BuildingContext, BuildingMaps, Built, DynBuildable, SavedBuildable,
+        WhichMap,
+    },
+    iso::{ClawMap, ItemContainerMap},
+    item::{spawn_item, ItemAnimator, ItemContainer, ItemContainerAlignment},
+    prelude::*,
+    resource_nodes::{self, ResourceNode, ResourceNodeMap, ResourceNodeSeed},
+    ui::{RecordedInput, Replay, ReplayEvent},
+};
+
+const QUICKSAVE_PATH: &str = "quicksave.ron";
+
+/// A save which will be loaded at the end of the UI stages this frame, for
+/// systems which cannot get at the whole world to load it themselves.
+#[derive(Default)]
+pub struct PendingLoad(pub Option<SaveFile>);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub resource_node_seed: u64,
+    #[serde(default)]
+    pub current_tick: u64,
+    pub buildings: Vec<SavedBuilding>,
+    pub containers: Vec<SavedContainer>,
+    pub storages: Vec<SavedStorage>,
+    pub claws: Vec<SavedClaw>,
+    pub machines: Vec<SavedMachine>,
+    #[serde(default)]
+    pub conveyors: Vec<SavedConveyor>,
+    #[serde(default)]
+    pub spawners: Vec<SavedSpawner>,
+    #[serde(default)]
+    pub tunnels: Vec<SavedTunnel>,
+}
+
+impl SaveFile {
+    /// Sorts every section by position. The order things are captured in
+    /// depends on the order they were spawned in, so this lets two captures
+    /// of the same factory be compared.
+    pub fn sorted(mut self) -> Self {
+        self.buildings.sort_by_key(|saved| saved.position);
+        self.containers.sort_by_key(|saved| saved.position);
+        self.storages.sort_by_key(|saved| saved.position);
+        self.claws
+            .sort_by_key(|saved| (saved.position, saved.index));
+        self.machines.sort_by_key(|saved| saved.position);
+        self.conveyors.sort_by_key(|saved| saved.position);
+        self.spawners.sort_by_key(|saved| saved.position);
+        self.tunnels.sort_by_key(|saved| saved.position);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedBuilding {
+    pub buildable: SavedBuildable,
+    pub position: IsoPos,
+    pub direction: IsoDirection,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedContainer {
+    pub position: IsoPos,
+    pub item: Option<Item>,
+    pub blocked: bool,
+    /// Where the item is moving to, if it has not arrived yet.
+    #[serde(default)]
+    pub animator: Option<ItemAnimator>,
+}
+
+/// Storages are identified by the position of the building that owns them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedStorage {
+    pub position: IsoPos,
+    pub items: ItemList,
+}
+
+/// Claws are identified by the position they move items to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedClaw {
+    pub position: IsoPos,
+    /// Which of the claws ending at `position` this is, counting in the order
+    /// they appear in `buildings`.
+    #[serde(default)]
+    pub index: usize,
+    pub current_anim_tick: u16,
+    pub blocked: bool,
+    pub held_item: Option<Item>,
+    #[serde(default)]
+    pub last_served: u64,
+}
+
+/// Machines are identified by the position of their origin.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedMachine {
+    pub position: IsoPos,
+    pub input_buffer: Vec<Option<Item>>,
+    pub processing_time: u8,
+}
+
+/// Conveyors are identified by their position.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedConveyor {
+    pub position: IsoPos,
+    pub incoming_timer: u8,
+    pub outgoing_timer: u8,
+    pub next_upstream: usize,
+    /// The position of the container the conveyor last pushed an item into,
+    /// while that item is still moving in.
+    #[serde(default)]
+    pub pushed_into: Option<IsoPos>,
+    /// Which output of a splitter gets the next item. Only set for splitters.
+    #[serde(default)]
+    pub next_output: Option<usize>,
+}
+
+/// Spawners are identified by their position.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSpawner {
+    pub position: IsoPos,
+    pub timer: u8,
+}
+
+/// Tunnels are identified by the position of their entrance. Only tunnels with
+/// items in them are saved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedTunnel {
+    pub position: IsoPos,
+    /// Each item along with the tick it reaches the exit, closest to the exit
+    /// first.
+    pub items: Vec<(Item, u64)>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(ron::Error),
+    /// The save refers to a kind of buildable which does not exist.
+    UnknownBuildable(String),
+    /// The save has state for something at this position which was not
+    /// rebuilt there, or which does not fit what was rebuilt there.
+    MismatchedState(IsoPos),
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        Self::Format(err)
+    }
+}
+
+pub fn write(path: impl AsRef<Path>, save: &SaveFile) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(save, Default::default())?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+pub fn read(path: impl AsRef<Path>) -> Result<SaveFile, SaveError> {
+    let text = fs::read_to_string(path)?;
+    Ok(ron::de::from_str(&text)?)
+}
+
+/// Records everything needed to rebuild the factory exactly as it is now.
+pub fn capture(world: &mut World) -> SaveFile {
+    let mut state: SystemState<(
+        Res<ResourceNodeSeed>,
+        Res<TickClock>,
+        Query<(&Built, Option<&ClawLogic>)>,
+        Query<(&IsoPos, &ItemContainer)>,
+        Query<(&IsoPos, &Storage)>,
+        Query<(&IsoPos, &MachineLogic)>,
+        Query<(&IsoPos, &ConveyorLogic, Option<&SplitterLogic>)>,
+        Query<(&IsoPos, &SpawnerLogic)>,
+        Query<(&IsoPos, &UndergroundEntrance)>,
+        Query<(&Item, &ItemAnimator)>,
+    )> = SystemState::new(world);
+    let (
+        seed,
+        tick_clock,
+        built,
+        containers,
+        storages,
+        machines,
+        conveyors,
+        spawners,
+        tunnels,
+        items,
+    ) = state.get(world);
+    let item = |entity: Entity| items.get(entity).unwrap().0.clone();
+    let animator = |entity: Entity| items.get(entity).unwrap().1.clone();
+    // Claws are rebuilt in the order they appear in the buildings, so that is
+    // the order several claws ending in the same place are counted in.
+    let mut claws_at = HashMap::new();
+    let claws = built
+        .iter()
+        .filter_map(|(built, claw)| Some((built.position, claw?)))
+        .map(|(position, claw)| {
+            let index = claws_at.entry(position).or_insert(0);
+            let saved = SavedClaw {
+                position,
+                index: *index,
+                current_anim_tick: claw.current_anim_tick(),
+                blocked: claw.blocked(),
+                held_item: claw.held_item().map(item),
+                last_served: claw.last_served(),
+            };
+            *index += 1;
+            saved
+        })
+        .collect();
+
+    SaveFile {
+        resource_node_seed: seed.0,
+        current_tick: tick_clock.current_tick(),
+        buildings: built
+            .iter()
+            .map(|(built, _)| SavedBuilding {
+                buildable: built.buildable.saved(),
+                position: built.position,
+                direction: built.direction,
+            })
+            .collect(),
+        containers: containers
+            .iter()
+            .filter(|(_, container)| container.item().is_some() || container.blocked())
+            .map(|(&position, container)| SavedContainer {
+                position,
+                item: container.item().map(item),
+                blocked: container.blocked(),
+                animator: container.item().map(animator),
+            })
+            .collect(),
+        storages: storages
+            .iter()
+            .map(|(&position, storage)| SavedStorage {
+                position,
+                items: storage.items().clone(),
+            })
+            .collect(),
+        claws,
+        machines: machines
+            .iter()
+            .map(|(&position, machine)| SavedMachine {
+                position,
+                input_buffer: machine.input_buffer().to_vec(),
+                processing_time: machine.processing_time(),
+            })
+            .collect(),
+        conveyors: conveyors
+            .iter()
+            .map(|(&position, conveyor, splitter)| SavedConveyor {
+                position,
+                incoming_timer: conveyor.incoming_timer(),
+                outgoing_timer: conveyor.outgoing_timer(),
+                next_upstream: conveyor.next_upstream(),
+                pushed_into: conveyor
+                    .pushed_into()
+                    .and_then(|container| containers.get(container).ok())
+                    .map(|(&position, _)| position),
+                next_output: splitter.map(SplitterLogic::next_output),
+            })
+            .collect(),
+        spawners: spawners
+            .iter()
+            .map(|(&position, spawner)| SavedSpawner {
+                position,
+                timer: spawner.timer(),
+            })
+            .collect(),
+        tunnels: tunnels
+            .iter()
+            .filter(|(_, tunnel)| tunnel.items().next().is_some())
+            .map(|(&position, tunnel)| SavedTunnel {
+                position,
+                items: tunnel
+                    .items()
+                    .map(|(entity, arrival)| (item(entity), arrival))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Replaces the current factory with the one described by `save`. Nothing
+/// is changed if any of the saved buildables cannot be loaded. If the rest of
+/// the saved state does not fit the buildings, the buildings are still loaded
+/// but their state may only be partly restored.
+pub fn load(world: &mut World, save: &SaveFile) -> Result<(), SaveError> {
+    let registry = world.get_resource::<BuildableRegistry>().unwrap();
+    let buildables = save
+        .buildings
+        .iter()
+        .map(|building| {
+            let saved = &building.buildable;
+            match registry.load(saved) {
+                Some(buildable) => Ok(buildable?),
+                None => Err(SaveError::UnknownBuildable(saved.id.clone())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    clear(world);
+    world.insert_resource(ResourceNodeSeed(save.resource_node_seed));
+    spawn_buildings(world, save, buildables);
+    world
+        .get_resource_mut::<TickClock>()
+        .unwrap()
+        .set_current_tick(save.current_tick);
+    restore_state(world, save)
+}
+
+fn clear(world: &mut World) {
+    let mut state: SystemState<(
+        Commands,
+        Query<Entity, With<Built>>,
+        Query<Entity, With<Item>>,
+        Query<Entity, With<ResourceNode>>,
+    )> = SystemState::new(world);
+    let (mut commands, built, items, resource_nodes) = state.get_mut(world);
+    for entity in built.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in items.iter().chain(resource_nodes.iter()) {
+        commands.entity(entity).despawn();
+    }
+    state.apply(world);
+
+    world.insert_resource(BuildingMap::default());
+    world.insert_resource(ClawMap::default());
+    world.insert_resource(ConveyorMap::default());
+    world.insert_resource(ItemContainerMap::default());
+    world.insert_resource(ResourceNodeMap::default());
+}
+
+fn spawn_buildings(world: &mut World, save: &SaveFile, buildables: Vec<Box<dyn DynBuildable>>) {
+    let mut state: SystemState<(Commands, Res<CommonAssets>, BuildingMaps)> =
+        SystemState::new(world);
+    let (mut commands, common_assets, mut maps) = state.get_mut(world);
+    let seed = ResourceNodeSeed(save.resource_node_seed);
+    resource_nodes::spawn_resource_nodes(&mut commands, &common_assets, &mut maps, seed);
+    // Claws look up the containers at both of their ends when they are
+    // spawned, so everything else has to exist first.
+    let (claws, others): (Vec<_>, Vec<_>) = buildables
+        .into_iter()
+        .zip(save.buildings.iter())
+        .partition(|(buildable, building)| {
+            buildable
+                .details(building.position, building.direction, &maps)
+                .map_or(false, |details| details.maps.contains(&WhichMap::Claws))
+        });
+    for (buildable, building) in others.into_iter().chain(claws) {
+        let mut ctx = BuildingContext {
+            commands: &mut commands,
+            position: building.position,
+            direction: building.direction,
+            common_assets: &common_assets,
+        };
+        spawn_buildable(buildable, &mut ctx, &mut maps);
+    }
+    state.apply(world);
+}
+
+fn restore_state(world: &mut World, save: &SaveFile) -> Result<(), SaveError> {
+    let mut state: SystemState<(
+        Commands,
+        Res<CommonAssets>,
+        BuildingMaps,
+        Query<(&IsoPos, &mut ItemContainer)>,
+        Query<&mut Storage>,
+        Query<&mut ClawLogic>,
+        Query<&mut Handle<StandardMaterial>>,
+        Query<&mut MachineLogic>,
+        Query<(&mut ConveyorLogic, Option<&mut SplitterLogic>)>,
+        Query<&mut SpawnerLogic>,
+        Query<&mut UndergroundEntrance>,
+    )> = SystemState::new(world);
+    let (
+        mut commands,
+        common_assets,
+        maps,
+        mut containers,
+        mut storages,
+        mut claws,
+        mut materials,
+        mut machines,
+        mut conveyors,
+        mut spawners,
+        mut tunnels,
+    ) = state.get_mut(world);
+    // Whatever was restored before a mismatch is kept, so that every item
+    // that was spawned ends up in the world.
+    let mut restore = || -> Result<(), SaveError> {
+        for saved in &save.containers {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &container = maps
+                .item_containers
+                .get(saved.position)
+                .ok_or_else(mismatched)?;
+            let (&pos, mut container) = containers.get_mut(container).map_err(|_| mismatched())?;
+            if container.item().is_some() {
+                return Err(mismatched());
+            }
+            if let Some(item) = &saved.item {
+                let alignment = container.alignment();
+                let item = spawn_item(&mut commands, &common_assets, item.clone(), pos, alignment);
+                if let Some(animator) = &saved.animator {
+                    commands.entity(item).insert(animator.clone());
+                }
+                container.put_item(item);
+            }
+            container.set_blocked(saved.blocked);
+        }
+        for saved in &save.storages {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &building = maps.buildings.get(saved.position).ok_or_else(mismatched)?;
+            let mut storage = storages.get_mut(building).map_err(|_| mismatched())?;
+            storage.set_items(saved.items.clone());
+        }
+        for saved in &save.claws {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &claw = maps
+                .claws
+                .get(saved.position)
+                .and_then(|claws| claws.get(saved.index))
+                .ok_or_else(mismatched)?;
+            let mut claw = claws.get_mut(claw).map_err(|_| mismatched())?;
+            let held_item = saved.held_item.clone().map(|item| {
+                if let Ok(mut material) = materials.get_mut(claw.head()) {
+                    *material = common_assets.claw_mat.1.clone();
+                }
+                spawn_item(
+                    &mut commands,
+                    &common_assets,
+                    item,
+                    saved.position,
+                    ItemContainerAlignment::Centroid,
+                )
+            });
+            claw.restore(
+                saved.current_anim_tick,
+                saved.blocked,
+                held_item,
+                saved.last_served,
+            );
+        }
+        for saved in &save.machines {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &building = maps.buildings.get(saved.position).ok_or_else(mismatched)?;
+            let mut machine = machines.get_mut(building).map_err(|_| mismatched())?;
+            if machine.input_buffer().len() != saved.input_buffer.len() {
+                return Err(mismatched());
+            }
+            machine.restore(saved.input_buffer.clone(), saved.processing_time);
+        }
+        for saved in &save.conveyors {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &conveyor = maps.conveyors.get(saved.position).ok_or_else(mismatched)?;
+            let (mut conveyor, splitter) = conveyors.get_mut(conveyor).map_err(|_| mismatched())?;
+            let pushed_into = saved
+                .pushed_into
+                .map(|position| maps.item_containers.get(position).ok_or_else(mismatched))
+                .transpose()?
+                .copied();
+            conveyor.restore(
+                saved.incoming_timer,
+                saved.outgoing_timer,
+                saved.next_upstream,
+                pushed_into,
+            );
+            match (saved.next_output, splitter) {
+                (Some(next_output), Some(mut splitter)) => splitter.restore(next_output),
+                (Some(_), None) => return Err(mismatched()),
+                (None, _) => {}
+            }
+        }
+        for saved in &save.spawners {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &building = maps.buildings.get(saved.position).ok_or_else(mismatched)?;
+            let mut spawner = spawners.get_mut(building).map_err(|_| mismatched())?;
+            spawner.restore(saved.timer);
+        }
+        for saved in &save.tunnels {
+            let mismatched = || SaveError::MismatchedState(saved.position);
+            let &entrance = maps.conveyors.get(saved.position).ok_or_else(mismatched)?;
+            let mut tunnel = tunnels.get_mut(entrance).map_err(|_| mismatched())?;
+            // Items underground are hidden until they come out of the exit.
+            let items = saved
+                .items
+                .iter()
+                .map(|(item, arrival)| {
+                    let item = spawn_item(
+                        &mut commands,
+                        &common_assets,
+                        item.clone(),
+                        saved.position,
+                        ItemContainerAlignment::Centroid,
+                    );
+                    let mut animator = ItemAnimator::new(saved.position.centroid_pos());
+                    animator.anim_hidden();
+                    commands.entity(item).insert(animator);
+                    (item, *arrival)
+                })
+                .collect();
+            tunnel.restore(items);
+        }
+        Ok(())
+    };
+    let result = restore();
+    state.apply(world);
+    result
+}
+
+/// Saving only writes a file, so it does not need to be replayed. Loading
+/// changes the whole factory, so it is recorded along with what was loaded.
+fn update(world: &mut World) {
+    let key_input = world.get_resource::<Input<KeyCode>>().unwrap();
+    let (save, load) = (
+        key_input.just_pressed(KeyCode::F6),
+        key_input.just_pressed(KeyCode::F9),
+    );
+    let replaying = world
+        .get_resource::<Replay>()
+        .map_or(false, Replay::is_playing);
+    if save {
+        if let Err(err) = write(QUICKSAVE_PATH, &capture(world)) {
+            error!("Failed to save to {}: {:?}", QUICKSAVE_PATH, err);
+        }
+    }
+    if load && !replaying {
+        let tick = world.get_resource::<TickClock>().unwrap().current_tick();
+        let result = read(QUICKSAVE_PATH).and_then(|save| {
+            let result = self::load(world, &save);
+            // A save whose state does not fit still replaces the factory.
+            if matches!(result, Ok(()) | Err(SaveError::MismatchedState(_))) {
+                if let Some(mut replay) = world.get_resource_mut::<Replay>() {
+                    replay.record(ReplayEvent {
+                        tick,
+                        input: RecordedInput::Load(save),
+                    });
+                }
+            }
+            result
+        });
+        if let Err(err) = result {
+            error!("Failed to load {}: {:?}", QUICKSAVE_PATH, err);
+        }
+    }
+    let pending = world.get_resource_mut::<PendingLoad>().unwrap().0.take();
+    if let Some(save) = pending {
+        if let Err(err) = self::load(world, &save) {
+            error!("Failed to load a replayed save: {:?}", err);
+        }
+    }
+}
+
+pub struct Plug;
+
+impl Plugin for Plug {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PendingLoad::default())
+            .add_system_to_stage(fstage::UI_POST, update.exclusive_system());
+    }
+}
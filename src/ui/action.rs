@@ -1,24 +1,43 @@
+mod conveyor_run;
 mod execute;
 mod ok;
 mod update;
 
 use bevy::prelude::*;
 
-pub use self::update::update;
+pub use self::{conveyor_run::ConveyorRun, update::update};
 use crate::{
     buildable::{
-        claw::BClaw,
-        conveyor::BConveyor,
+        claw::{BClaw, BWaypointClaw, ClawStop, StopKind},
+        conveyor::ConveyorTier,
         machine::{BMachine, MachineType},
         Buildable, BuildingContext, DynBuildable, storage::ItemList,
     },
+    item::ItemFilter,
     prelude::*,
 };
 
 pub enum Action {
-    PlaceConveyor,
-    PlaceClawStart,
-    PlaceClawEnd { take_from: IsoPos },
+    PlaceConveyor(ConveyorTier),
+    PlaceConveyorRun(ConveyorRun),
+    PlaceClawStart {
+        filter: ItemFilter,
+        priority: u8,
+    },
+    PlaceClawEnd {
+        take_from: IsoPos,
+        filter: ItemFilter,
+        priority: u8,
+    },
+    /// Each click adds a stop, and clicking the last stop again builds the
+    /// claw.
+    PlaceWaypointClaw {
+        stops: Vec<ClawStop>,
+        /// What the next stop does.
+        kind: StopKind,
+        filter: ItemFilter,
+        priority: u8,
+    },
     PlaceBuildable(Box<dyn DynBuildable>),
     Destroy,
 }
@@ -26,6 +45,9 @@ pub enum Action {
 pub struct ActionState {
     pub action: Action,
     pub ok: bool,
+    /// Why the action cannot be executed, when there is more to it than the
+    /// cursor being in the wrong place.
+    pub problem: Option<String>,
     pub required_items: ItemList,
     preview: Vec<Entity>,
 }
@@ -33,14 +55,21 @@ pub struct ActionState {
 impl Action {
     pub fn get_snapping(&self, selected_direction: IsoDirection) -> Snapping {
         match self {
-            Self::PlaceConveyor => Snapping::None,
-            Self::PlaceClawStart => Snapping::None,
+            Self::PlaceConveyor(..) => Snapping::None,
+            Self::PlaceConveyorRun(run) => run.snapping(),
+            Self::PlaceClawStart { .. } => Snapping::None,
             Self::PlaceClawEnd {
                 take_from: start_pos,
                 ..
             } => Snapping::AlongAnyLine {
                 through: *start_pos,
             },
+            Self::PlaceWaypointClaw { stops, .. } => match stops.last() {
+                Some(stop) => Snapping::AlongAnyLine {
+                    through: stop.position,
+                },
+                None => Snapping::None,
+            },
             Self::PlaceBuildable(..) => Snapping::require_edge_pointing_in(selected_direction),
             Self::Destroy => Snapping::None,
         }
@@ -48,22 +77,99 @@ impl Action {
 
     pub fn spawn_art(&self, ctx: &mut BuildingContext) -> Vec<Entity> {
         match self {
-            Self::PlaceConveyor => BConveyor.spawn_art(ctx),
-            Self::PlaceClawStart => BClaw {
+            Self::PlaceConveyor(tier) => tier.buildable().dyn_spawn_art(ctx),
+            Self::PlaceConveyorRun(run) => {
+                let conveyor = run.tier().buildable();
+                let conveyors = run.conveyors(ctx.position, ctx.direction);
+                let mut art = Vec::new();
+                for (position, direction) in conveyors.unwrap_or_default() {
+                    art.append(&mut conveyor.dyn_spawn_art(&mut BuildingContext {
+                        commands: &mut *ctx.commands,
+                        position,
+                        direction,
+                        common_assets: ctx.common_assets,
+                    }));
+                }
+                art
+            }
+            &Self::PlaceClawStart {
+                ref filter,
+                priority,
+            } => BClaw {
                 take_from: ctx.position,
+                filter: filter.clone(),
+                priority,
+            }
+            .spawn_art(ctx),
+            &Self::PlaceClawEnd {
+                take_from,
+                ref filter,
+                priority,
+            } => BClaw {
+                take_from,
+                filter: filter.clone(),
+                priority,
             }
             .spawn_art(ctx),
-            &Self::PlaceClawEnd { take_from } => BClaw { take_from }.spawn_art(ctx),
+            Self::PlaceWaypointClaw { .. } => {
+                let (claw, _) = self.waypoint_claw(ctx.position).unwrap();
+                claw.spawn_art(ctx)
+            }
             Self::PlaceBuildable(bld) => bld.dyn_spawn_art(ctx),
             Self::Destroy => vec![],
         }
     }
+
+    /// The claw that placing a waypoint claw at `position` works towards,
+    /// along with whether doing so builds it rather than adding another stop.
+    pub fn waypoint_claw(&self, position: IsoPos) -> Option<(BWaypointClaw, bool)> {
+        if let Self::PlaceWaypointClaw {
+            stops,
+            kind,
+            filter,
+            priority,
+        } = self
+        {
+            let finishing =
+                stops.len() >= 2 && stops.last().map(|stop| stop.position) == Some(position);
+            let mut stops = stops.clone();
+            if !finishing {
+                stops.push(ClawStop {
+                    position,
+                    kind: *kind,
+                    filter: filter.clone(),
+                });
+            }
+            let claw = BWaypointClaw {
+                stops,
+                priority: *priority,
+            };
+            Some((claw, finishing))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the mouse input means the action should be executed this
+    /// frame. Conveyors are dragged out from where the button is pressed to
+    /// where it is released.
+    pub fn is_triggered(&self, input: &Input<MouseButton>) -> bool {
+        match self {
+            Self::PlaceConveyor(..) => input.just_pressed(MouseButton::Left),
+            Self::PlaceConveyorRun(..) => input.just_released(MouseButton::Left),
+            // Holding the button down would finish the claw right after
+            // adding a stop.
+            Self::PlaceWaypointClaw { .. } => input.just_pressed(MouseButton::Left),
+            _ => input.pressed(MouseButton::Left),
+        }
+    }
 }
 
 pub fn startup(mut commands: Commands) {
     commands.insert_resource(ActionState {
-        action: Action::PlaceConveyor,
+        action: Action::PlaceConveyor(ConveyorTier::Normal),
         ok: false,
+        problem: None,
         required_items: ItemList::new(),
         preview: vec![],
     })
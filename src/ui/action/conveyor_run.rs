@@ -0,0 +1,81 @@
+mod tests;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{buildable::conveyor::ConveyorTier, prelude::*};
+
+/// A line of conveyors being dragged out by the player. The run starts where
+/// the mouse was pressed and follows one axis at a time. Rotating the cursor
+/// while dragging turns the run at the position the cursor was at.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConveyorRun {
+    tier: ConveyorTier,
+    /// The start of each straight section of the run and the axis it follows.
+    legs: Vec<(IsoPos, IsoAxis)>,
+}
+
+impl ConveyorRun {
+    pub fn new(tier: ConveyorTier, start: IsoPos, axis: IsoAxis) -> Self {
+        Self {
+            tier,
+            legs: vec![(start, axis)],
+        }
+    }
+
+    pub fn tier(&self) -> ConveyorTier {
+        self.tier
+    }
+
+    /// The line the end of the run has to be on.
+    pub fn snapping(&self) -> Snapping {
+        let &(through, axis) = self.legs.last().unwrap();
+        Snapping::AlongLine { through, axis }
+    }
+
+    /// Starts a new straight section at `corner` if `axis` is not the one the
+    /// run is currently following.
+    pub fn turn(&mut self, corner: IsoPos, axis: IsoAxis) {
+        let &(_, current_axis) = self.legs.last().unwrap();
+        if axis != current_axis {
+            self.legs.push((corner, axis));
+        }
+    }
+
+    /// Returns the position of every conveyor in the run along with the
+    /// direction it has to face to feed the next one, or None if the run
+    /// can't end at `end` or crosses itself. The last conveyor keeps going in
+    /// the direction the run was heading, or faces `direction` if the run is a
+    /// single conveyor.
+    pub fn conveyors(
+        &self,
+        end: IsoPos,
+        direction: IsoDirection,
+    ) -> Option<Vec<(IsoPos, IsoDirection)>> {
+        let mut result = Vec::new();
+        let mut heading = direction;
+        let leg_ends = self.legs.iter().skip(1).map(|&(pos, _)| pos);
+        for (&(start, axis), leg_end) in self.legs.iter().zip(leg_ends.chain(Some(end))) {
+            let steps = start.steps_along(axis, leg_end)?;
+            if steps == 0 {
+                continue;
+            }
+            heading = if steps > 0 {
+                axis.positive_direction()
+            } else {
+                axis.negative_direction()
+            };
+            for step in 0..steps.abs() {
+                result.push((start.offset_direction(heading, step), heading));
+            }
+        }
+        result.push((end, heading));
+
+        let mut positions: Vec<_> = result.iter().map(|&(pos, _)| pos).collect();
+        positions.sort();
+        positions.dedup();
+        if positions.len() < result.len() {
+            return None;
+        }
+        Some(result)
+    }
+}
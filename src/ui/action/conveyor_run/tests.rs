@@ -0,0 +1,106 @@
+#![cfg(test)]
+
+use bevy::ecs::system::SystemState;
+
+use super::*;
+use crate::{
+    buildable::{storage::ItemList, BuildingMaps},
+    headless,
+    ui::action::ok::conveyor_run_ok,
+};
+
+#[test]
+fn single_conveyor_faces_the_cursor() {
+    let start = IsoPos::origin();
+    let run = ConveyorRun::new(ConveyorTier::Normal, start, IsoAxis::A);
+    assert_eq!(
+        run.conveyors(start, IsoDirection::PosB),
+        Some(vec![(start, IsoDirection::PosB)])
+    );
+}
+
+#[test]
+fn straight_run() {
+    let start = IsoPos::origin();
+    let run = ConveyorRun::new(ConveyorTier::Normal, start, IsoAxis::A);
+    let expected: Vec<_> = (0..4)
+        .map(|step| (start.offset_a(step), IsoDirection::PosA))
+        .collect();
+    assert_eq!(
+        run.conveyors(start.offset_a(3), IsoDirection::PosB),
+        Some(expected)
+    );
+    // Dragging the other way makes every conveyor face back towards the end.
+    let expected: Vec<_> = (0..3)
+        .map(|step| (start.offset_a(-step), IsoDirection::NegA))
+        .collect();
+    assert_eq!(
+        run.conveyors(start.offset_a(-2), IsoDirection::PosB),
+        Some(expected)
+    );
+    // The end has to be on the line the run is following.
+    assert_eq!(run.conveyors(start.offset_b(2), IsoDirection::PosB), None);
+}
+
+#[test]
+fn l_shaped_run() {
+    let start = IsoPos::origin();
+    let corner = start.offset_a(3);
+    let end = corner.offset_b(2);
+    let mut run = ConveyorRun::new(ConveyorTier::Normal, start, IsoAxis::A);
+    run.turn(corner, IsoAxis::B);
+    // Turning onto the axis the run already follows does nothing.
+    run.turn(end, IsoAxis::B);
+    assert_eq!(
+        run.conveyors(end, IsoDirection::PosA),
+        Some(vec![
+            (start, IsoDirection::PosA),
+            (start.offset_a(1), IsoDirection::PosA),
+            (start.offset_a(2), IsoDirection::PosA),
+            (corner, IsoDirection::PosB),
+            (corner.offset_b(1), IsoDirection::PosB),
+            (end, IsoDirection::PosB),
+        ])
+    );
+}
+
+#[test]
+fn run_crossing_itself_is_none() {
+    let start = IsoPos::origin();
+    let corner = start.offset_a(4);
+    let mut run = ConveyorRun::new(ConveyorTier::Normal, start, IsoAxis::A);
+    run.turn(corner, IsoAxis::B);
+    run.turn(corner, IsoAxis::A);
+    // Heading back along the first leg puts two conveyors on the same cells.
+    assert_eq!(run.conveyors(start.offset_a(1), IsoDirection::PosA), None);
+}
+
+#[test]
+fn run_costs_every_conveyor() {
+    let mut app = headless::app();
+    let mut state: SystemState<BuildingMaps> = SystemState::new(&mut app.world);
+    let maps = state.get_mut(&mut app.world);
+    let start = IsoPos::origin();
+    let corner = start.offset_a(3);
+    let mut run = ConveyorRun::new(ConveyorTier::Fast, start, IsoAxis::A);
+    run.turn(corner, IsoAxis::B);
+    let conveyors = run
+        .conveyors(corner.offset_b(2), IsoDirection::PosA)
+        .unwrap();
+    assert_eq!(conveyors.len(), 6);
+
+    let (space_ok, details) = conveyor_run_ok(run.tier(), &conveyors, &maps);
+    let details = details.unwrap();
+    assert!(space_ok);
+    assert_eq!(details.shape.len(), conveyors.len());
+    let one = ConveyorTier::Fast
+        .buildable()
+        .details(start, IsoDirection::PosA, &maps)
+        .unwrap()
+        .cost;
+    let mut expected = ItemList::new();
+    for _ in &conveyors {
+        expected.add_list(&one);
+    }
+    assert_eq!(details.cost, expected);
+}
@@ -1,15 +1,16 @@
 use bevy::prelude::*;
 
-use super::{Action, ActionState};
+use super::{Action, ActionState, ConveyorRun};
 use crate::{
     buildable::{
         self,
-        claw::BClaw,
-        conveyor::BConveyor,
+        claw::{BClaw, BWaypointClaw, StopKind},
+        conveyor::ConveyorTier,
         destroy_buildable,
         machine::{BMachine, MachineType},
         spawn_buildable, BuildingContext, BuildingMaps, Built, DynBuildable, storage::Storage,
     },
+    item::ItemFilter,
     prelude::*,
     ui::cursor::CursorState,
 };
@@ -29,14 +30,59 @@ pub fn execute_action(
         direction: cursor_state.direction,
         common_assets: &*common_assets,
     };
-    for (mut storage,) in storages.iter_mut() {
-        storage.subtract_available_inventory_from_self_and(&mut action_state.required_items);
+    // Starting a run only marks where it starts, the conveyors are paid for
+    // when it is placed. The same goes for the stops of a waypoint claw.
+    let only_marks = match action_state.action.waypoint_claw(cursor_state.world_pos) {
+        Some((_, finishing)) => !finishing,
+        None => matches!(action_state.action, Action::PlaceConveyor(..)),
+    };
+    if !only_marks {
+        for (mut storage,) in storages.iter_mut() {
+            storage.subtract_available_inventory_from_self_and(&mut action_state.required_items);
+        }
     }
     match &action_state.action {
-        Action::PlaceConveyor => execute_place_conveyor(&mut ctx, &mut maps),
-        Action::PlaceClawStart => execute_place_claw_start(cursor_state, action_state),
-        &Action::PlaceClawEnd { take_from } => {
-            execute_place_claw_end(cursor_state, take_from, &mut ctx, &mut maps, action_state)
+        &Action::PlaceConveyor(tier) => {
+            execute_start_conveyor_run(tier, cursor_state, action_state)
+        }
+        Action::PlaceConveyorRun(run) => {
+            let tier = run.tier();
+            let conveyors = run.conveyors(cursor_state.world_pos, cursor_state.direction);
+            execute_place_conveyor_run(tier, conveyors.unwrap(), &mut ctx, &mut maps, action_state)
+        }
+        &Action::PlaceClawStart {
+            ref filter,
+            priority,
+        } => {
+            let filter = filter.clone();
+            execute_place_claw_start(cursor_state, filter, priority, action_state)
+        }
+        &Action::PlaceClawEnd {
+            take_from,
+            ref filter,
+            priority,
+        } => {
+            let claw = BClaw {
+                take_from,
+                filter: filter.clone(),
+                priority,
+            };
+            execute_place_claw_end(cursor_state, claw, &mut ctx, &mut maps, action_state)
+        }
+        &Action::PlaceWaypointClaw {
+            kind, ref filter, ..
+        } => {
+            let filter = filter.clone();
+            let (claw, finishing) = action_state.action.waypoint_claw(ctx.position).unwrap();
+            execute_place_waypoint_claw(
+                claw,
+                finishing,
+                kind,
+                filter,
+                &mut ctx,
+                &mut maps,
+                action_state,
+            )
         }
         Action::PlaceBuildable(bld) => execute_place_buildable(bld, ctx, maps),
         Action::Destroy => execute_destroy(built, ctx, maps),
@@ -46,7 +92,9 @@ pub fn execute_action(
 
 fn execute_destroy(built: Query<&Built>, mut ctx: BuildingContext, mut maps: BuildingMaps) {
     let pos = ctx.position;
-    let ent = *maps.claws.get(pos).or(maps.buildings.get(pos)).unwrap();
+    // Claws are on top, and the one built last goes first.
+    let claw = maps.claws.get(pos).and_then(|claws| claws.last());
+    let ent = *claw.or(maps.buildings.get(pos)).unwrap();
     let built = built.get(ent).unwrap();
     destroy_buildable((ent, built), &mut ctx, &mut maps)
 }
@@ -61,28 +109,81 @@ fn execute_place_buildable(bld: &Box<dyn DynBuildable>, mut ctx: BuildingContext
 
 fn execute_place_claw_end(
     cursor_state: &CursorState,
-    take_from: IsoPos,
+    claw: BClaw,
     ctx: &mut BuildingContext,
     maps: &mut BuildingMaps,
     action_state: &mut ResMut<ActionState>,
 ) {
     if let Some(_) = cursor_state.hovered_container {
-        spawn_buildable(Box::new(BClaw { take_from }), ctx, maps);
-        action_state.action = Action::PlaceClawStart;
+        let (filter, priority) = (claw.filter.clone(), claw.priority);
+        spawn_buildable(Box::new(claw), ctx, maps);
+        action_state.action = Action::PlaceClawStart { filter, priority };
     }
 }
 
+fn execute_place_waypoint_claw(
+    claw: BWaypointClaw,
+    finishing: bool,
+    kind: StopKind,
+    filter: ItemFilter,
+    ctx: &mut BuildingContext,
+    maps: &mut BuildingMaps,
+    action_state: &mut ResMut<ActionState>,
+) {
+    let priority = claw.priority;
+    let stops = if finishing {
+        spawn_buildable(Box::new(claw), ctx, maps);
+        vec![]
+    } else {
+        claw.stops
+    };
+    action_state.action = Action::PlaceWaypointClaw {
+        stops,
+        kind,
+        filter,
+        priority,
+    };
+}
+
 fn execute_place_claw_start(
     cursor_state: &CursorState,
+    filter: ItemFilter,
+    priority: u8,
     action_state: &mut ResMut<ActionState>,
 ) {
     if let Some(_) = cursor_state.hovered_container {
         action_state.action = Action::PlaceClawEnd {
             take_from: cursor_state.world_pos,
+            filter,
+            priority,
         };
     }
 }
 
-fn execute_place_conveyor(ctx: &mut BuildingContext, maps: &mut BuildingMaps) {
-    buildable::spawn_buildable(Box::new(BConveyor), ctx, maps);
+fn execute_start_conveyor_run(
+    tier: ConveyorTier,
+    cursor_state: &CursorState,
+    action_state: &mut ResMut<ActionState>,
+) {
+    action_state.action = Action::PlaceConveyorRun(ConveyorRun::new(
+        tier,
+        cursor_state.world_pos,
+        cursor_state.direction.axis(),
+    ));
+}
+
+/// Places the whole run at once, so that it is never left half built.
+fn execute_place_conveyor_run(
+    tier: ConveyorTier,
+    conveyors: Vec<(IsoPos, IsoDirection)>,
+    ctx: &mut BuildingContext,
+    maps: &mut BuildingMaps,
+    action_state: &mut ResMut<ActionState>,
+) {
+    for (position, direction) in conveyors {
+        ctx.position = position;
+        ctx.direction = direction;
+        buildable::spawn_buildable(tier.buildable(), ctx, maps);
+    }
+    action_state.action = Action::PlaceConveyor(tier);
 }
@@ -5,40 +5,69 @@ use super::{Action, ActionState};
 use crate::{
     buildable::{
         claw::BClaw,
-        conveyor::BConveyor,
+        conveyor::ConveyorTier,
         storage::{ItemList, Storage},
         Buildable, BuildingDetails, BuildingMaps,
     },
     item::ReferenceItem,
+    prelude::*,
     ui::cursor::CursorState,
 };
 
 pub fn update_action_ok(
     action_state: &mut ResMut<ActionState>,
     maps: &BuildingMaps,
-    cursor_state: &Res<CursorState>,
+    cursor_state: &CursorState,
     storages: &Query<(&mut Storage,)>,
 ) {
     let position = cursor_state.world_pos;
     let direction = cursor_state.direction;
+    let mut problem = None;
     let (prereqs_ok, deets) = match &action_state.action {
-        Action::PlaceConveyor => (
+        &Action::PlaceConveyor(tier) => (
             !maps.buildings.is_occupied(position),
-            BConveyor.details(position, direction, maps),
-        ),
-        Action::PlaceClawStart => (
-            !maps.claws.is_occupied(position) && cursor_state.hovered_container.is_some(),
-            BClaw {
-                take_from: position,
-            }
-            .details(position, direction, maps),
-        ),
-        &Action::PlaceClawEnd { take_from } => (
-            !maps.claws.is_occupied(position)
-                && cursor_state.hovered_container.is_some()
-                && position != take_from,
-            BClaw { take_from }.details(position, direction, maps),
+            tier.buildable().details(position, direction, maps),
         ),
+        Action::PlaceConveyorRun(run) => match run.conveyors(position, direction) {
+            Some(conveyors) => conveyor_run_ok(run.tier(), &conveyors, maps),
+            None => (false, None),
+        },
+        &Action::PlaceClawStart {
+            ref filter,
+            priority,
+        } => {
+            let checked = BClaw::check_end(position, maps);
+            problem = checked.err();
+            (
+                checked.is_ok(),
+                BClaw {
+                    take_from: position,
+                    filter: filter.clone(),
+                    priority,
+                }
+                .details(position, direction, maps),
+            )
+        }
+        &Action::PlaceClawEnd {
+            take_from,
+            ref filter,
+            priority,
+        } => {
+            let claw = BClaw {
+                take_from,
+                filter: filter.clone(),
+                priority,
+            };
+            let checked = claw.check_placement(position, maps);
+            problem = checked.err();
+            (checked.is_ok(), claw.details(position, direction, maps))
+        }
+        Action::PlaceWaypointClaw { .. } => {
+            let (claw, _) = action_state.action.waypoint_claw(position).unwrap();
+            let checked = claw.check_placement(maps);
+            problem = checked.err();
+            (checked.is_ok(), claw.details(position, direction, maps))
+        }
         Action::PlaceBuildable(bld) => {
             let deets = bld.details(position, direction, maps);
             let shape = deets.as_ref().map(|x| &x.shape[..]).unwrap_or(&[]);
@@ -62,6 +91,7 @@ pub fn update_action_ok(
         ),
     };
     action_state.ok = prereqs_ok;
+    action_state.problem = problem.map(|problem| problem.to_string());
     if let Some(deets) = deets {
         let required_items = deets.cost;
         let mut required_items_not_in_storage = required_items.clone();
@@ -75,3 +105,30 @@ pub fn update_action_ok(
         action_state.required_items = ItemList::new();
     }
 }
+
+/// A run can only be placed if there is room for every conveyor in it, and it
+/// costs as much as all of them together.
+pub(super) fn conveyor_run_ok(
+    tier: ConveyorTier,
+    conveyors: &[(IsoPos, IsoDirection)],
+    maps: &BuildingMaps,
+) -> (bool, Option<BuildingDetails>) {
+    let mut space_ok = true;
+    let mut deets = BuildingDetails {
+        shape: vec![],
+        maps: vec![],
+        cost: ItemList::new(),
+    };
+    let conveyor = tier.buildable();
+    for &(position, direction) in conveyors {
+        space_ok &= !maps.buildings.is_occupied(position);
+        match conveyor.details(position, direction, maps) {
+            Some(conveyor) => {
+                deets.shape.extend(conveyor.shape);
+                deets.cost.add_list(&conveyor.cost);
+            }
+            None => return (false, None),
+        }
+    }
+    (space_ok, Some(deets))
+}
@@ -3,13 +3,23 @@ use bevy::prelude::*;
 use super::{Action, ActionState};
 use crate::{
     buildable::{
+        claw::{BClaw, StopKind},
+        conveyor::{
+            BSorter, BSplitter, BUndergroundEntrance, BUndergroundExit, ConveyorTier,
+            MAX_UNDERGROUND_LENGTH,
+        },
         drill::BDrill,
         machine::{BMachine, MachineType},
         storage::{BSmallWarehouse, ItemList, Storage},
-        BuildingContext, BuildingMaps, Built,
+        Buildable, BuildableRegistry, BuildingContext, BuildingMaps, Built,
     },
+    item::{ItemFilter, ItemRule},
     prelude::*,
-    ui::cursor::CursorState,
+    save::PendingLoad,
+    ui::{
+        cursor::CursorState,
+        replay::{RecordedAction, RecordedInput, Replay, ReplayEvent},
+    },
 };
 
 pub fn update(
@@ -22,20 +32,73 @@ pub fn update(
     maps: BuildingMaps,
     built: Query<&Built>,
     storages: Query<(&mut Storage,)>,
+    mut replay: ResMut<Replay>,
+    mut tick_clock: ResMut<TickClock>,
+    mut pending_load: ResMut<PendingLoad>,
+    registry: Res<BuildableRegistry>,
+    mut choices: Local<Choices>,
 ) {
-    super::ok::update_action_ok(&mut action_state, &maps, &cursor_state, &storages);
-    if input.pressed(MouseButton::Left) && action_state.ok {
-        super::execute::execute_action(
-            &mut commands,
-            &cursor_state,
-            &common_assets,
-            &mut action_state,
-            maps,
-            built,
-            storages,
-        );
-    }
-    handle_change_action_input(key_input, &mut action_state);
+    if replay.is_playing() {
+        if let Some(event) = replay.take_due(tick_clock.current_tick()) {
+            match event.input {
+                RecordedInput::Action {
+                    position,
+                    direction,
+                    action,
+                } => {
+                    let cursor_state = cursor_state.moved_to(position, direction, &maps);
+                    replay_action(
+                        &mut commands,
+                        event.tick,
+                        action,
+                        &cursor_state,
+                        &common_assets,
+                        &mut action_state,
+                        &registry,
+                        maps,
+                        built,
+                        storages,
+                    );
+                }
+                RecordedInput::Clock(change) => change.apply(&mut tick_clock),
+                // Loading needs the whole world, so it happens later on.
+                RecordedInput::Load(save) => pending_load.0 = Some(save),
+            }
+        }
+        tick_clock.set_tick_limit(replay.next_tick());
+    } else {
+        if let Action::PlaceConveyorRun(run) = &mut action_state.action {
+            run.turn(cursor_state.world_pos, cursor_state.direction.axis());
+        }
+        super::ok::update_action_ok(&mut action_state, &maps, &cursor_state, &storages);
+        if action_state.action.is_triggered(&input) && action_state.ok {
+            replay.record(ReplayEvent {
+                tick: tick_clock.current_tick(),
+                input: RecordedInput::Action {
+                    position: cursor_state.world_pos,
+                    direction: cursor_state.direction,
+                    action: RecordedAction::new(&action_state.action),
+                },
+            });
+            super::execute::execute_action(
+                &mut commands,
+                &cursor_state,
+                &common_assets,
+                &mut action_state,
+                maps,
+                built,
+                storages,
+            );
+        }
+        // A run that could not be placed when the button was released is
+        // abandoned.
+        if let Action::PlaceConveyorRun(run) = &action_state.action {
+            if !input.pressed(MouseButton::Left) {
+                action_state.action = Action::PlaceConveyor(run.tier());
+            }
+        }
+        handle_change_action_input(key_input, &mut action_state, &mut choices);
+    }
     update_preview(
         &mut commands,
         &mut action_state,
@@ -44,15 +107,69 @@ pub fn update(
     );
 }
 
-fn handle_change_action_input(key_input: Res<Input<KeyCode>>, action_state: &mut ActionState) {
+/// Executes an action from a replay the same way it was originally executed.
+fn replay_action(
+    commands: &mut Commands,
+    tick: u64,
+    action: RecordedAction,
+    cursor_state: &CursorState,
+    common_assets: &CommonAssets,
+    action_state: &mut ResMut<ActionState>,
+    registry: &BuildableRegistry,
+    maps: BuildingMaps,
+    built: Query<&Built>,
+    storages: Query<(&mut Storage,)>,
+) {
+    action_state.action = match action.to_action(registry) {
+        Ok(action) => action,
+        Err(err) => {
+            error!("Failed to replay action after tick {}: {:?}", tick, err);
+            return;
+        }
+    };
+    super::ok::update_action_ok(action_state, &maps, cursor_state, &storages);
+    if !action_state.ok {
+        warn!("Replayed action after tick {} is no longer possible", tick);
+        return;
+    }
+    super::execute::execute_action(
+        commands,
+        cursor_state,
+        common_assets,
+        action_state,
+        maps,
+        built,
+        storages,
+    );
+}
+
+/// What the player last picked for buildings that can be set up before they
+/// are placed.
+#[derive(Default)]
+pub struct Choices {
+    underground_length: u8,
+    rule: ItemRule,
+    claw_filter: ItemFilter,
+    claw_priority: u8,
+    stop_kind: StopKind,
+}
+
+fn handle_change_action_input(
+    key_input: Res<Input<KeyCode>>,
+    action_state: &mut ActionState,
+    choices: &mut Choices,
+) {
     if key_input.just_pressed(KeyCode::Grave) {
         action_state.action = Action::Destroy
     }
     if key_input.just_pressed(KeyCode::Key1) {
-        action_state.action = Action::PlaceConveyor
+        action_state.action = Action::PlaceConveyor(ConveyorTier::Normal)
     }
     if key_input.just_pressed(KeyCode::Key2) {
-        action_state.action = Action::PlaceClawStart;
+        action_state.action = Action::PlaceClawStart {
+            filter: choices.claw_filter.clone(),
+            priority: choices.claw_priority,
+        };
     }
     if key_input.just_pressed(KeyCode::Key3) {
         action_state.action = Action::PlaceBuildable(Box::new(BMachine(MachineType::Purifier)));
@@ -66,6 +183,133 @@ fn handle_change_action_input(key_input: Res<Input<KeyCode>>, action_state: &mut
     if key_input.just_pressed(KeyCode::Key6) {
         action_state.action = Action::PlaceBuildable(Box::new(BDrill));
     }
+    if key_input.just_pressed(KeyCode::Key7) {
+        action_state.action = Action::PlaceBuildable(Box::new(BSplitter {
+            prioritize_front: false,
+        }));
+    }
+    if key_input.just_pressed(KeyCode::Key8) {
+        action_state.action = Action::PlaceBuildable(Box::new(BSplitter {
+            prioritize_front: true,
+        }));
+    }
+    if key_input.just_pressed(KeyCode::Key9) {
+        action_state.action = Action::PlaceConveyor(ConveyorTier::Slow)
+    }
+    if key_input.just_pressed(KeyCode::Key0) {
+        action_state.action = Action::PlaceConveyor(ConveyorTier::Fast)
+    }
+    if key_input.just_pressed(KeyCode::U) {
+        // Pressing it again makes the tunnel one cell longer, until it wraps
+        // back around to the shortest useful tunnel.
+        let length = &mut choices.underground_length;
+        *length = if (2..MAX_UNDERGROUND_LENGTH).contains(length) {
+            *length + 1
+        } else {
+            2
+        };
+        action_state.action =
+            Action::PlaceBuildable(Box::new(BUndergroundEntrance { length: *length }));
+    }
+    if key_input.just_pressed(KeyCode::I) {
+        action_state.action = Action::PlaceBuildable(Box::new(BUndergroundExit));
+    }
+    if key_input.just_pressed(KeyCode::J) {
+        action_state.action = Action::PlaceWaypointClaw {
+            stops: vec![],
+            kind: choices.stop_kind,
+            filter: choices.claw_filter.clone(),
+            priority: choices.claw_priority,
+        };
+    }
+    handle_rule_input(&key_input, action_state, choices);
+}
+
+/// P switches what kind of rule is being set up and the brackets change what
+/// it looks for. O places a sorter using the rule, while F adds it to the
+/// filter of the claws being placed, G switches the filter between allowing
+/// and denying what it lists and H clears it. T sets the filter to exactly
+/// the item the rule names, if it names one. Y raises the priority of the
+/// claws being placed, wrapping back around to zero. K switches the next stop
+/// of a waypoint claw between picking up and dropping off.
+fn handle_rule_input(
+    key_input: &Input<KeyCode>,
+    action_state: &mut ActionState,
+    choices: &mut Choices,
+) {
+    let placing_sorter =
+        matches!(&action_state.action, Action::PlaceBuildable(bld) if bld.id() == BSorter::ID);
+    let mut rule_changed = false;
+    if key_input.just_pressed(KeyCode::P) {
+        choices.rule = choices.rule.next_kind();
+        rule_changed = true;
+    }
+    if key_input.just_pressed(KeyCode::LBracket) {
+        choices.rule = choices.rule.adjusted(-1);
+        rule_changed = true;
+    }
+    if key_input.just_pressed(KeyCode::RBracket) {
+        choices.rule = choices.rule.adjusted(1);
+        rule_changed = true;
+    }
+    if key_input.just_pressed(KeyCode::O) || (rule_changed && placing_sorter) {
+        action_state.action = Action::PlaceBuildable(Box::new(BSorter { rule: choices.rule }));
+    }
+
+    let mut claw_changed = true;
+    if key_input.just_pressed(KeyCode::F) {
+        choices.claw_filter.add_rule(choices.rule);
+    } else if key_input.just_pressed(KeyCode::G) {
+        choices.claw_filter.invert();
+    } else if key_input.just_pressed(KeyCode::H) {
+        choices.claw_filter = ItemFilter::Any;
+    } else if key_input.just_pressed(KeyCode::T) {
+        if let ItemRule::Is(known) = choices.rule {
+            choices.claw_filter = ItemFilter::Only(known);
+        }
+    } else if key_input.just_pressed(KeyCode::Y) {
+        choices.claw_priority = (choices.claw_priority + 1) % (BClaw::MAX_PRIORITY + 1);
+    } else if key_input.just_pressed(KeyCode::K) {
+        choices.stop_kind = choices.stop_kind.toggled();
+    } else {
+        claw_changed = false;
+    }
+    if claw_changed {
+        // A claw that has already been started keeps its starting point.
+        match &mut action_state.action {
+            Action::PlaceClawEnd {
+                filter, priority, ..
+            } => {
+                *filter = choices.claw_filter.clone();
+                *priority = choices.claw_priority;
+            }
+            // Stops which have already been placed keep their own settings.
+            Action::PlaceWaypointClaw {
+                kind,
+                filter,
+                priority,
+                ..
+            } => {
+                *kind = choices.stop_kind;
+                *filter = choices.claw_filter.clone();
+                *priority = choices.claw_priority;
+            }
+            action if key_input.just_pressed(KeyCode::K) => {
+                *action = Action::PlaceWaypointClaw {
+                    stops: vec![],
+                    kind: choices.stop_kind,
+                    filter: choices.claw_filter.clone(),
+                    priority: choices.claw_priority,
+                }
+            }
+            action => {
+                *action = Action::PlaceClawStart {
+                    filter: choices.claw_filter.clone(),
+                    priority: choices.claw_priority,
+                }
+            }
+        }
+    }
 }
 
 fn update_preview(
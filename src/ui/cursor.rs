@@ -13,6 +13,25 @@ pub struct CursorState {
     world_cursor: Entity,
 }
 
+impl CursorState {
+    /// A copy of this cursor which points at `world_pos` in `direction`, as
+    /// if the player had moved it there.
+    pub fn moved_to(
+        &self,
+        world_pos: IsoPos,
+        direction: IsoDirection,
+        maps: &BuildingMaps,
+    ) -> Self {
+        Self {
+            pos: self.pos,
+            world_pos,
+            direction,
+            hovered_container: maps.item_containers.get(world_pos).copied(),
+            world_cursor: self.world_cursor,
+        }
+    }
+}
+
 pub fn startup(mut commands: Commands, assets: Res<CommonAssets>) {
     let world_cursor = commands
         .spawn()
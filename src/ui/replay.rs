@@ -0,0 +1,229 @@
+//! Records every action the player executes, every change they make to the
+//! clock and every save they load, along with the tick each one happened
+//! after, so that a session can be played back exactly. Inputs are always
+//! handled between ticks, so replaying them after the same ticks (and making
+//! sure a single frame never runs past the next one) reproduces the original
+//! factory.
+
+mod tests;
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{LineWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    action::{Action, ConveyorRun},
+    speed::ClockChange,
+};
+use crate::{
+    buildable::{
+        claw::{ClawStop, StopKind},
+        conveyor::ConveyorTier,
+        BuildableRegistry, SavedBuildable,
+    },
+    item::ItemFilter,
+    prelude::*,
+    save::{SaveError, SaveFile},
+};
+
+/// A serializable version of an Action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedAction {
+    PlaceConveyor(ConveyorTier),
+    PlaceConveyorRun(ConveyorRun),
+    PlaceClawStart {
+        #[serde(default)]
+        filter: ItemFilter,
+        #[serde(default)]
+        priority: u8,
+    },
+    PlaceClawEnd {
+        take_from: IsoPos,
+        #[serde(default)]
+        filter: ItemFilter,
+        #[serde(default)]
+        priority: u8,
+    },
+    PlaceWaypointClaw {
+        stops: Vec<ClawStop>,
+        kind: StopKind,
+        filter: ItemFilter,
+        priority: u8,
+    },
+    PlaceBuildable(SavedBuildable),
+    Destroy,
+}
+
+impl RecordedAction {
+    pub fn new(action: &Action) -> Self {
+        match action {
+            &Action::PlaceConveyor(tier) => Self::PlaceConveyor(tier),
+            Action::PlaceConveyorRun(run) => Self::PlaceConveyorRun(run.clone()),
+            &Action::PlaceClawStart {
+                ref filter,
+                priority,
+            } => Self::PlaceClawStart {
+                filter: filter.clone(),
+                priority,
+            },
+            &Action::PlaceClawEnd {
+                take_from,
+                ref filter,
+                priority,
+            } => Self::PlaceClawEnd {
+                take_from,
+                filter: filter.clone(),
+                priority,
+            },
+            &Action::PlaceWaypointClaw {
+                ref stops,
+                kind,
+                ref filter,
+                priority,
+            } => Self::PlaceWaypointClaw {
+                stops: stops.clone(),
+                kind,
+                filter: filter.clone(),
+                priority,
+            },
+            Action::PlaceBuildable(bld) => Self::PlaceBuildable(bld.saved()),
+            Action::Destroy => Self::Destroy,
+        }
+    }
+
+    pub fn to_action(&self, registry: &BuildableRegistry) -> Result<Action, SaveError> {
+        Ok(match self {
+            &Self::PlaceConveyor(tier) => Action::PlaceConveyor(tier),
+            Self::PlaceConveyorRun(run) => Action::PlaceConveyorRun(run.clone()),
+            &Self::PlaceClawStart {
+                ref filter,
+                priority,
+            } => Action::PlaceClawStart {
+                filter: filter.clone(),
+                priority,
+            },
+            &Self::PlaceClawEnd {
+                take_from,
+                ref filter,
+                priority,
+            } => Action::PlaceClawEnd {
+                take_from,
+                filter: filter.clone(),
+                priority,
+            },
+            &Self::PlaceWaypointClaw {
+                ref stops,
+                kind,
+                ref filter,
+                priority,
+            } => Action::PlaceWaypointClaw {
+                stops: stops.clone(),
+                kind,
+                filter: filter.clone(),
+                priority,
+            },
+            Self::PlaceBuildable(saved) => match registry.load(saved) {
+                Some(bld) => Action::PlaceBuildable(bld?),
+                None => return Err(SaveError::UnknownBuildable(saved.id.clone())),
+            },
+            Self::Destroy => Action::Destroy,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    /// The input happened after this tick and before the next one.
+    pub tick: u64,
+    pub input: RecordedInput,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedInput {
+    /// An action executed with the cursor at `position`.
+    Action {
+        position: IsoPos,
+        direction: IsoDirection,
+        action: RecordedAction,
+    },
+    Clock(ClockChange),
+    /// A save was loaded. The whole save is kept so that the replay does not
+    /// depend on the file still being there.
+    Load(SaveFile),
+}
+
+pub enum Replay {
+    Off,
+    /// Each event is written to the file at `path` on a line of its own as
+    /// soon as it happens, so the file is still useful if the game crashes.
+    Recording {
+        path: PathBuf,
+        file: LineWriter<File>,
+    },
+    /// Player input is ignored until all of these have been executed.
+    Playing(VecDeque<ReplayEvent>),
+}
+
+impl Replay {
+    pub fn record_to(path: impl Into<PathBuf>) -> Result<Self, SaveError> {
+        let path = path.into();
+        let file = LineWriter::new(File::create(&path)?);
+        Ok(Self::Recording { path, file })
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let text = fs::read_to_string(path)?;
+        let events = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ron::de::from_str::<ReplayEvent>)
+            .collect::<Result<_, _>>()?;
+        Ok(Self::Playing(events))
+    }
+
+    pub fn is_playing(&self) -> bool {
+        match self {
+            Self::Playing(events) => !events.is_empty(),
+            _ => false,
+        }
+    }
+
+    pub fn record(&mut self, event: ReplayEvent) {
+        if let Self::Recording { path, file } = self {
+            let result = ron::ser::to_string(&event)
+                .map_err(SaveError::from)
+                .and_then(|line| Ok(writeln!(file, "{}", line)?));
+            if let Err(err) = result {
+                error!("Failed to write replay to {:?}: {:?}", path, err);
+            }
+        }
+    }
+
+    /// The tick the next replayed input has to happen after.
+    pub fn next_tick(&self) -> Option<u64> {
+        match self {
+            Self::Playing(events) => events.front().map(|event| event.tick),
+            _ => None,
+        }
+    }
+
+    /// Returns the next replayed input if it should happen now. Only one is
+    /// returned per frame, because that is all the player could have done.
+    pub fn take_due(&mut self, current_tick: u64) -> Option<ReplayEvent> {
+        if let Self::Playing(events) = self {
+            if events
+                .front()
+                .map_or(false, |event| event.tick <= current_tick)
+            {
+                return events.pop_front();
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,125 @@
+#![cfg(test)]
+
+use super::*;
+use crate::{
+    buildable::{
+        destroyer::BDestroyer,
+        spawner::BSpawner,
+        storage::{BSmallWarehouse, ItemList},
+    },
+    headless,
+    item::ReferenceItem,
+    save,
+    ui::{
+        action::{self, ActionState},
+        cursor::{self, CursorState},
+        speed,
+    },
+};
+
+/// A headless app which also runs the systems that record and replay input.
+/// Input only ever comes from the test, since there is no window.
+fn app(replay: Replay) -> App {
+    let mut app = headless::app();
+    app.insert_resource(Input::<MouseButton>::default())
+        .insert_resource(Input::<KeyCode>::default())
+        .insert_resource(replay)
+        .add_startup_system(action::startup.system())
+        .add_startup_system(cursor::startup.system())
+        .add_system_to_stage(fstage::UI, action::update.system())
+        .add_system_to_stage(fstage::UI, speed::update.system())
+        .add_plugin(save::Plug);
+    let mut items = ItemList::new();
+    items.add_bulk(ReferenceItem::IronLump.as_item(), 300);
+    items.add_bulk(ReferenceItem::PureAnimus.as_item(), 300);
+    headless::build(
+        &mut app,
+        BSmallWarehouse(items),
+        IsoPos::new(-4, 8),
+        IsoDirection::PosB,
+    );
+    headless::run_ticks(&mut app, 1);
+    app
+}
+
+/// Clicks once with the cursor at `position`, running a single frame.
+fn click(app: &mut App, action: Action, position: IsoPos, direction: IsoDirection) {
+    app.world.get_resource_mut::<ActionState>().unwrap().action = action;
+    let mut cursor_state = app.world.get_resource_mut::<CursorState>().unwrap();
+    cursor_state.world_pos = position;
+    cursor_state.direction = direction;
+    let mut input = app.world.get_resource_mut::<Input<MouseButton>>().unwrap();
+    input.press(MouseButton::Left);
+    app.update();
+    let mut input = app.world.get_resource_mut::<Input<MouseButton>>().unwrap();
+    input.release(MouseButton::Left);
+    input.clear();
+}
+
+/// Presses a key for a single frame.
+fn press(app: &mut App, key: KeyCode) {
+    let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+    input.press(key);
+    app.update();
+    let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+    input.release(key);
+    input.clear();
+}
+
+fn current_tick(app: &App) -> u64 {
+    app.world
+        .get_resource::<TickClock>()
+        .unwrap()
+        .current_tick()
+}
+
+#[test]
+fn replay_reproduces_session() {
+    let path = std::env::temp_dir().join("factory_replay_reproduces_session.ron");
+    let mut recording = app(Replay::record_to(&path).unwrap());
+    let source = IsoPos::origin();
+    let belt = |distance| source.offset_direction(IsoDirection::PosA, distance);
+    let spawner = BSpawner {
+        item: ReferenceItem::Magnetite,
+        interval: 8,
+    };
+    click(
+        &mut recording,
+        Action::PlaceBuildable(Box::new(spawner)),
+        source,
+        IsoDirection::PosA,
+    );
+    headless::run_ticks(&mut recording, 5);
+    for distance in 1..4 {
+        let action = Action::PlaceConveyor(ConveyorTier::Normal);
+        click(&mut recording, action, belt(distance), IsoDirection::PosA);
+    }
+    headless::run_ticks(&mut recording, 7);
+    let action = Action::PlaceBuildable(Box::new(BDestroyer));
+    click(&mut recording, action, belt(4), IsoDirection::PosA);
+    headless::run_ticks(&mut recording, 20);
+    press(&mut recording, KeyCode::Space);
+    headless::run_ticks(&mut recording, 3);
+    press(&mut recording, KeyCode::Period);
+    headless::run_ticks(&mut recording, 3);
+    press(&mut recording, KeyCode::Space);
+    headless::run_ticks(&mut recording, 30);
+    let end = current_tick(&recording);
+    let recorded = save::capture(&mut recording.world);
+    drop(recording);
+
+    let mut playing = app(Replay::read(&path).unwrap());
+    for _ in 0..1000 {
+        if current_tick(&playing) >= end {
+            break;
+        }
+        playing.update();
+    }
+    let replay = playing.world.get_resource::<Replay>().unwrap();
+    assert!(!replay.is_playing());
+    assert_eq!(current_tick(&playing), end);
+    let replayed = save::capture(&mut playing.world);
+    fs::remove_file(&path).unwrap();
+    assert!(!recorded.conveyors.is_empty());
+    assert_eq!(replayed.sorted(), recorded.sorted());
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::replay::{RecordedInput, Replay, ReplayEvent};
+use crate::prelude::*;
+
+/// Anything the player can do to the clock which changes when ticks happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClockChange {
+    TogglePaused,
+    Step,
+    SetSpeed(u8),
+}
+
+impl ClockChange {
+    pub fn apply(self, tick_clock: &mut TickClock) {
+        match self {
+            Self::TogglePaused => tick_clock.toggle_paused(),
+            Self::Step => tick_clock.request_step(),
+            Self::SetSpeed(speed) => tick_clock.set_speed(speed),
+        }
+    }
+}
+
+pub fn update(
+    key_input: Res<Input<KeyCode>>,
+    mut tick_clock: ResMut<TickClock>,
+    mut replay: ResMut<Replay>,
+) {
+    // Interpolation only changes how things are drawn, so it is never
+    // recorded and can be changed while a replay is playing.
+    if key_input.just_pressed(KeyCode::F5) {
+        tick_clock.toggle_interpolation();
+    }
+    if replay.is_playing() {
+        return;
+    }
+    let mut changes = Vec::new();
+    if key_input.just_pressed(KeyCode::Space) {
+        changes.push(ClockChange::TogglePaused);
+    }
+    if key_input.just_pressed(KeyCode::Period) {
+        changes.push(ClockChange::Step);
+    }
+    for &(key, speed) in &[
+        (KeyCode::F1, 1),
+        (KeyCode::F2, 2),
+        (KeyCode::F3, 4),
+        (KeyCode::F4, 8),
+    ] {
+        if key_input.just_pressed(key) {
+            changes.push(ClockChange::SetSpeed(speed));
+        }
+    }
+    for change in changes {
+        change.apply(&mut tick_clock);
+        replay.record(ReplayEvent {
+            tick: tick_clock.current_tick(),
+            input: RecordedInput::Clock(change),
+        });
+    }
+}
@@ -5,7 +5,7 @@ use super::{
     cursor::CursorState,
 };
 use crate::{
-    buildable::{storage::Storage, BuildingMaps},
+    buildable::{claw::ClawLogic, stats::Throughput, storage::Storage, BuildingMaps, Built},
     item::ItemContainer,
     prelude::*,
 };
@@ -50,21 +50,45 @@ pub fn update_post(
     maps: BuildingMaps,
     containers: Query<&ItemContainer>,
     warehouses: Query<(&Storage, &IsoPos)>,
+    throughputs: Query<&Throughput>,
+    built: Query<(&Built, Option<&ClawLogic>)>,
     mut texts: Query<&mut Text>,
     items: Query<&Item>,
     action_state: Res<ActionState>,
     cursor_state: Res<CursorState>,
     tooltip_state: Res<TooltipState>,
+    tick_clock: Res<TickClock>,
 ) {
     let hovered_container = maps.item_containers.get(cursor_state.world_pos).copied();
 
     let tooltip = match &action_state.action {
-        Action::PlaceClawStart => format!("Claw Start"),
-        Action::PlaceClawEnd { .. } => format!("Claw End"),
-        Action::PlaceConveyor => format!("Conveyor"),
-        Action::PlaceBuildable(bld) => format!("{:?}", bld),
+        Action::PlaceClawStart { filter, priority } => {
+            format!("Claw Start ({}, priority {})", filter, priority)
+        }
+        Action::PlaceClawEnd {
+            filter, priority, ..
+        } => format!("Claw End ({}, priority {})", filter, priority),
+        Action::PlaceWaypointClaw {
+            stops,
+            kind,
+            filter,
+            priority,
+        } => format!(
+            "Waypoint Claw Stop {} ({} {}, priority {})",
+            stops.len() + 1,
+            kind,
+            filter,
+            priority
+        ),
+        Action::PlaceConveyor(tier) => tier.buildable().display_name(),
+        Action::PlaceConveyorRun(run) => format!("{} Run", run.tier().buildable().display_name()),
+        Action::PlaceBuildable(bld) => bld.display_name(),
         Action::Destroy => format!("Destroy"),
     };
+    let tooltip = match &action_state.problem {
+        Some(problem) => format!("{}: {}", tooltip, problem),
+        None => tooltip,
+    };
     let mut text = texts.get_mut(tooltip_state.tool_text).unwrap();
     let hovered_item = if let Some(container) = hovered_container {
         if let Some(item) = containers.get(container).unwrap().item() {
@@ -82,9 +106,45 @@ pub fn update_post(
             hovered_warehouse = warehouse.summary();
         }
     }
+    // Claws sit on top of other buildings, and several of them can end in
+    // the same place.
+    let claws = maps.claws.get(cursor_state.world_pos).into_iter().flatten();
+    let hovered_building = maps
+        .buildings
+        .get(cursor_state.world_pos)
+        .into_iter()
+        .chain(claws)
+        .filter_map(|&building| built.get(building).ok())
+        .map(|(built, claw)| {
+            let name = built.buildable.display_name();
+            match claw {
+                Some(claw) if !claw.is_attached() => format!("{} (unattached)", name),
+                _ => name,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let hovered_throughput = maps
+        .buildings
+        .get(cursor_state.world_pos)
+        .and_then(|&building| throughputs.get(building).ok())
+        .map(|throughput| throughput.summary(tick_clock.current_tick()).summary())
+        .unwrap_or_default();
+    let speed = if tick_clock.is_paused() {
+        "Paused".to_owned()
+    } else {
+        format!("{}x", tick_clock.speed())
+    };
     let action_cost = action_state.required_items.summary();
     text.sections[0].value = format!(
-        "{}\n{}\n{}\n{}\nCost:\n{}",
-        tooltip, /* credits.0.floor() */ 0, hovered_item, hovered_warehouse, action_cost
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\nCost:\n{}",
+        speed,
+        tooltip,
+        /* credits.0.floor() */ 0,
+        hovered_building,
+        hovered_item,
+        hovered_warehouse,
+        hovered_throughput,
+        action_cost
     );
 }